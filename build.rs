@@ -0,0 +1,30 @@
+use std::process::Command;
+
+fn main() {
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    println!("cargo:rerun-if-env-changed=PSD_GIT_HASH");
+
+    let git_hash = std::env::var("PSD_GIT_HASH")
+        .ok()
+        .or_else(|| output_of("git", &["rev-parse", "--short=12", "HEAD"]))
+        .unwrap_or_else(|| "unknown".to_string());
+
+    let rustc = std::env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+    let rustc_version = output_of(&rustc, &["--version"]).unwrap_or_else(|| "unknown".to_string());
+
+    println!("cargo:rustc-env=PSD_GIT_HASH={}", git_hash);
+    println!(
+        "cargo:rustc-env=PSD_TARGET={}",
+        std::env::var("TARGET").expect("cargo sets TARGET")
+    );
+    println!("cargo:rustc-env=PSD_RUSTC_VERSION={}", rustc_version);
+}
+
+fn output_of(cmd: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(cmd).args(args).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let s = String::from_utf8(output.stdout).ok()?;
+    Some(s.trim().to_string())
+}
@@ -0,0 +1,196 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use serde_json::Value;
+
+use crate::cell::Cell;
+use crate::normalize_query;
+use crate::replay::{Item, Reader};
+use crate::writer::{FileOpener, Line, OutputFormat, SnapshotWriter};
+
+/// Copy an output file, replacing the roles, client addresses, client host names and
+/// application names with made-up ones, and the literals in queries with `?`, and dropping
+/// where the server keeps its data, so it can be shared.
+pub fn anonymize(input: &str, output: &str) -> Result<()> {
+    let format = OutputFormat::from_path(output)?;
+    let mut writer = SnapshotWriter::create(
+        &FileOpener,
+        output.to_string(),
+        format,
+        "pg_stat_activity".to_string(),
+        None,
+        None,
+    )?;
+    let mut anonymizer = Anonymizer::default();
+    for item in Reader::open(input)? {
+        match item? {
+            Item::Snapshot { when, lines } if lines.is_empty() => {
+                writer.write_line(&Line {
+                    when,
+                    records: Vec::new(),
+                })?;
+            }
+            Item::Snapshot { when, mut lines } => {
+                anonymizer.anonymize(&mut lines);
+                writer.write_snapshot(when, &lines)?;
+            }
+            Item::Other(mut value) => {
+                scrub_header(&mut value);
+                writer.write_line(&value)?
+            }
+            Item::Comment(comment) => writer.write_comment(&comment)?,
+        }
+    }
+    writer.finish()?;
+    Ok(())
+}
+
+/// The made-up value for each real one, so a value is replaced the same way throughout a file.
+#[derive(Default)]
+struct Anonymizer {
+    users: HashMap<String, String>,
+    addrs: HashMap<String, String>,
+    hosts: HashMap<String, String>,
+    apps: HashMap<String, String>,
+}
+
+impl Anonymizer {
+    fn anonymize(&mut self, lines: &mut [Vec<Cell>]) {
+        let (headers, rows) = lines.split_first_mut().expect("header row");
+        for (col, header) in headers.iter().enumerate() {
+            for row in rows.iter_mut() {
+                let value = &mut row[col];
+                // background workers have no user, and local connections no address
+                if value.is_empty() {
+                    continue;
+                }
+                *value = Cell::from(match header.as_str() {
+                    "usename" => replace(&mut self.users, value, |n| format!("user_{}", n)),
+                    "client_addr" => replace(&mut self.addrs, value, |n| {
+                        format!("10.{}.{}.{}", (n >> 16) & 0xff, (n >> 8) & 0xff, n & 0xff)
+                    }),
+                    "application_name" => replace(&mut self.apps, value, |n| format!("app_{}", n)),
+                    // not a hash, which a list of likely host names would reverse
+                    "client_hostname" => replace(&mut self.hosts, value, |n| format!("host_{}", n)),
+                    "query" => normalize_query(value),
+                    _ => continue,
+                });
+            }
+        }
+    }
+}
+
+/// `value`'s replacement, making up the `n`th (from 1) if it's new.
+fn replace(seen: &mut HashMap<String, String>, value: &str, make: fn(usize) -> String) -> String {
+    let next = seen.len() + 1;
+    seen.entry(value.to_string())
+        .or_insert_with(|| make(next))
+        .to_string()
+}
+
+/// A header line says which server a file is from: where its data directory is, and the exact
+/// build it runs, e.g. `15.4 (Debian 15.4-1.pgdg120+1)`, which becomes `15.4`. Other lines are
+/// left alone.
+fn scrub_header(value: &mut Value) {
+    let Some(header) = value
+        .as_object_mut()
+        .filter(|line| line.contains_key("pg_data_directory"))
+    else {
+        return;
+    };
+    header.insert("pg_data_directory".to_string(), Value::Null);
+    if let Some(Value::String(version)) = header.get_mut("pg_version") {
+        version.truncate(version.find(' ').unwrap_or(version.len()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{scrub_header, Anonymizer};
+    use crate::printer::table;
+
+    #[test]
+    fn consistent_replacements() {
+        let headers: &[&str] = &[
+            "pid",
+            "usename",
+            "client_addr",
+            "client_hostname",
+            "application_name",
+            "query",
+        ];
+        let mut anonymizer = Anonymizer::default();
+        let mut first = table(&[
+            headers,
+            &[
+                "1",
+                "alice",
+                "192.168.1.5",
+                "alice-laptop",
+                "billing",
+                "select * from t where name = 'alice'",
+            ],
+            &["2", "bob", "", "", "", ""],
+        ]);
+        anonymizer.anonymize(&mut first);
+        let mut second = table(&[
+            headers,
+            &[
+                "3",
+                "bob",
+                "192.168.1.5",
+                "alice-laptop",
+                "psql",
+                "select 1",
+            ],
+        ]);
+        anonymizer.anonymize(&mut second);
+
+        assert_eq!(
+            table(&[
+                headers,
+                &[
+                    "1",
+                    "user_1",
+                    "10.0.0.1",
+                    "host_1",
+                    "app_1",
+                    "select * from t where name = ?"
+                ],
+                &["2", "user_2", "", "", "", ""],
+            ]),
+            first
+        );
+        assert_eq!(
+            table(&[
+                headers,
+                &["3", "user_2", "10.0.0.1", "host_1", "app_2", "select ?"]
+            ]),
+            second
+        );
+    }
+
+    #[test]
+    fn scrubbed_header() {
+        let mut header = json!({
+            "pg_version": "15.4 (Debian 15.4-1.pgdg120+1)",
+            "pg_data_directory": "/var/lib/postgresql/15/billing-primary",
+            "started_at": "2024-01-02T03:04:05Z",
+        });
+        scrub_header(&mut header);
+        assert_eq!(
+            json!({
+                "pg_version": "15.4",
+                "pg_data_directory": null,
+                "started_at": "2024-01-02T03:04:05Z",
+            }),
+            header
+        );
+
+        let mut footer = json!({ "wait_event_histogram": [] });
+        scrub_header(&mut footer);
+        assert_eq!(json!({ "wait_event_histogram": [] }), footer);
+    }
+}
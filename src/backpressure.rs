@@ -0,0 +1,171 @@
+use std::io::Write;
+use std::sync::mpsc::{self, SyncSender, TrySendError};
+use std::thread::JoinHandle;
+
+use anyhow::{anyhow, bail, Result};
+use bunyarrs::{vars, Bunyarr};
+use serde_json::json;
+
+/// What to write, and whether to flush afterwards.
+type Chunk = (Vec<u8>, bool);
+
+/// Writes to `inner` on a background thread, through a queue of `capacity` chunks, so slow
+/// compression doesn't hold up polling; when the queue is full, `try_write` gives up on the
+/// chunk instead of waiting. Chunks are bytes, not strings, for msgpack. Without a capacity,
+/// everything is written immediately, on the caller's thread.
+pub struct BackpressureWriter<W> {
+    /// Here whenever the thread isn't running.
+    inner: Option<W>,
+    running: Option<(SyncSender<Chunk>, JoinHandle<std::io::Result<W>>)>,
+    capacity: Option<usize>,
+    dropped: u64,
+}
+
+impl<W: Write + Send + 'static> BackpressureWriter<W> {
+    pub fn new(inner: W, capacity: Option<usize>) -> BackpressureWriter<W> {
+        BackpressureWriter {
+            inner: Some(inner),
+            running: None,
+            capacity,
+            dropped: 0,
+        }
+    }
+
+    /// Write `buf`, waiting for space in the queue if necessary.
+    pub fn write(&mut self, buf: Vec<u8>, flush: bool) -> Result<()> {
+        let Some(sender) = self.sender() else {
+            return write_chunk(self.inner_mut()?, (buf, flush)).map_err(Into::into);
+        };
+        if sender.send((buf, flush)).is_err() {
+            self.stop()?;
+            bail!("background writer stopped");
+        }
+        Ok(())
+    }
+
+    /// Write `buf`, unless the queue is full; returns whether it was accepted.
+    pub fn try_write(&mut self, buf: Vec<u8>, flush: bool) -> Result<bool> {
+        let Some(sender) = self.sender() else {
+            write_chunk(self.inner_mut()?, (buf, flush))?;
+            return Ok(true);
+        };
+        match sender.try_send((buf, flush)) {
+            Ok(()) => Ok(true),
+            Err(TrySendError::Full(_)) => {
+                self.dropped += 1;
+                let dropped = self.dropped;
+                Bunyarr::with_name("writer")
+                    .warn(vars! { dropped }, "write queue full, dropping snapshot");
+                Ok(false)
+            }
+            Err(TrySendError::Disconnected(_)) => {
+                self.stop()?;
+                bail!("background writer stopped");
+            }
+        }
+    }
+
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    /// Waits for everything queued to be written; the thread starts again on the next write.
+    pub fn inner_mut(&mut self) -> Result<&mut W> {
+        self.stop()?;
+        Ok(self.inner.as_mut().expect("stopped writer has its inner"))
+    }
+
+    pub fn into_inner(mut self) -> Result<W> {
+        self.stop()?;
+        Ok(self.inner.take().expect("stopped writer has its inner"))
+    }
+
+    fn sender(&mut self) -> Option<SyncSender<Chunk>> {
+        let capacity = self.capacity?;
+        if self.running.is_none() {
+            let mut inner = self.inner.take().expect("idle writer has its inner");
+            let (sender, receiver) = mpsc::sync_channel::<Chunk>(capacity);
+            let thread = std::thread::spawn(move || {
+                for chunk in receiver {
+                    write_chunk(&mut inner, chunk)?;
+                }
+                Ok(inner)
+            });
+            self.running = Some((sender, thread));
+        }
+        self.running.as_ref().map(|(sender, _)| sender.clone())
+    }
+
+    fn stop(&mut self) -> Result<()> {
+        let Some((sender, thread)) = self.running.take() else {
+            return Ok(());
+        };
+        // the thread's loop ends once the queue is empty and there are no senders
+        drop(sender);
+        let inner = thread
+            .join()
+            .map_err(|_| anyhow!("background writer panicked"))?
+            .map_err(|err| anyhow!(err).context("writing in the background"))?;
+        self.inner = Some(inner);
+        Ok(())
+    }
+}
+
+fn write_chunk(inner: &mut impl Write, (buf, flush): Chunk) -> std::io::Result<()> {
+    inner.write_all(&buf)?;
+    if flush {
+        inner.flush()?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{self, Write};
+    use std::sync::mpsc::{self, Receiver};
+
+    use super::BackpressureWriter;
+
+    /// Each write waits for a go-ahead, like a very slow disk.
+    struct Gated {
+        gate: Receiver<()>,
+        written: Vec<u8>,
+    }
+
+    impl Write for Gated {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.gate.recv().expect("gate open");
+            self.written.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn drops_when_full() {
+        let (open, gate) = mpsc::channel();
+        let gated = Gated {
+            gate,
+            written: Vec::new(),
+        };
+        let mut writer = BackpressureWriter::new(gated, Some(1));
+
+        let mut accepted = Vec::new();
+        for i in 0..10u8 {
+            if writer.try_write(vec![i], true).expect("writer running") {
+                accepted.push(i);
+            }
+        }
+        // one being written, and one in the queue, at most
+        assert!((1..=2).contains(&accepted.len()), "{:?}", accepted);
+        assert_eq!(10 - accepted.len() as u64, writer.dropped());
+
+        for _ in 0..accepted.len() {
+            open.send(()).expect("writer waiting");
+        }
+        assert_eq!(accepted, writer.into_inner().expect("clean stop").written);
+    }
+}
@@ -0,0 +1,95 @@
+use std::collections::HashMap;
+
+/// Each waiting pid, and the pids `pg_blocking_pids()` says it is waiting for.
+pub type Edges = HashMap<i32, Vec<i32>>;
+
+/// The longest chain of sessions each waiting for the next, starting with the one waiting on
+/// the others; e.g. `[a, b, c]` if `a` waits for `b`, which waits for `c`. Deadlocks form
+/// loops, which are cut where they would come back round, so each pid is only visited once;
+/// a queue of sessions reporting everyone ahead of them is quick to walk.
+pub fn longest_chain(edges: &Edges) -> Vec<i32> {
+    let mut waiters: Vec<i32> = edges.keys().copied().collect();
+    // for a stable answer between equally long chains
+    waiters.sort_unstable();
+
+    let mut visits = HashMap::new();
+    let mut longest: Option<(usize, i32)> = None;
+    for waiter in waiters {
+        let len = chain_len(edges, waiter, &mut visits).expect("no chain left unfinished");
+        if longest.is_none_or(|(longest, _)| len > longest) {
+            longest = Some((len, waiter));
+        }
+    }
+
+    let mut chain = Vec::new();
+    let mut next = longest.map(|(_, waiter)| waiter);
+    while let Some(pid) = next {
+        chain.push(pid);
+        next = match visits[&pid] {
+            Visit::Done { next, .. } => next,
+            Visit::Visiting => unreachable!("every chain is finished"),
+        };
+    }
+    chain
+}
+
+enum Visit {
+    Visiting,
+    /// The length of the longest chain starting here, and the pid it goes on to.
+    Done {
+        len: usize,
+        next: Option<i32>,
+    },
+}
+
+/// `None` if `pid` is already being visited, i.e. we've gone round a loop.
+fn chain_len(edges: &Edges, pid: i32, visits: &mut HashMap<i32, Visit>) -> Option<usize> {
+    match visits.get(&pid) {
+        Some(Visit::Done { len, .. }) => return Some(*len),
+        Some(Visit::Visiting) => return None,
+        None => (),
+    }
+    visits.insert(pid, Visit::Visiting);
+
+    let (mut len, mut next) = (1, None);
+    for &blocker in edges.get(&pid).into_iter().flatten() {
+        if let Some(blocker_len) = chain_len(edges, blocker, visits) {
+            if blocker_len + 1 > len {
+                (len, next) = (blocker_len + 1, Some(blocker));
+            }
+        }
+    }
+    visits.insert(pid, Visit::Done { len, next });
+    Some(len)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{longest_chain, Edges};
+
+    #[test]
+    fn follows_blockers() {
+        let mut edges = Edges::new();
+        edges.insert(1, vec![2]);
+        edges.insert(2, vec![3, 4]);
+        edges.insert(4, vec![5]);
+        edges.insert(6, vec![5]);
+        assert_eq!(vec![1, 2, 4, 5], longest_chain(&edges));
+    }
+
+    #[test]
+    fn survives_deadlocks() {
+        let mut edges = Edges::new();
+        edges.insert(1, vec![2]);
+        edges.insert(2, vec![1]);
+        assert_eq!(vec![1, 2], longest_chain(&edges));
+        assert!(longest_chain(&Edges::new()).is_empty());
+    }
+
+    #[test]
+    fn queues() {
+        // each UPDATE of the same row reports every one queued ahead of it
+        let edges: Edges = (1..40).map(|pid| (pid, (0..pid).collect())).collect();
+        assert_eq!((0..40).rev().collect::<Vec<_>>(), longest_chain(&edges));
+    }
+}
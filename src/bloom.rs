@@ -0,0 +1,64 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+/// A set of strings which can say "definitely not seen" or "probably seen", in a fixed amount
+/// of memory, however many are added.
+pub struct Bloom {
+    bits: Vec<u64>,
+    hashes: u32,
+}
+
+impl Bloom {
+    /// `bits` is rounded up to a multiple of 64.
+    pub fn new(bits: usize, hashes: u32) -> Bloom {
+        assert!(bits > 0 && hashes > 0);
+        Bloom {
+            bits: vec![0; bits.div_ceil(64)],
+            hashes,
+        }
+    }
+
+    /// Add `value`, returning whether it was (probably) already there.
+    pub fn insert(&mut self, value: &str) -> bool {
+        let mut hasher = DefaultHasher::new();
+        value.hash(&mut hasher);
+        let hash = hasher.finish();
+        // double hashing: the i'th probe is `h1 + i * h2`
+        let (h1, h2) = (hash & 0xffff_ffff, (hash >> 32) | 1);
+
+        let len = self.bits.len() as u64 * 64;
+        let mut present = true;
+        for i in 0..u64::from(self.hashes) {
+            let bit = h1.wrapping_add(i.wrapping_mul(h2)) % len;
+            let (word, mask) = ((bit / 64) as usize, 1 << (bit % 64));
+            present &= self.bits[word] & mask != 0;
+            self.bits[word] |= mask;
+        }
+        present
+    }
+
+    pub fn clear(&mut self) {
+        self.bits.fill(0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Bloom;
+
+    #[test]
+    fn remembers() {
+        let mut bloom = Bloom::new(1 << 16, 4);
+        assert!(!bloom.insert("select 1"));
+        assert!(bloom.insert("select 1"));
+        assert!(!bloom.insert("select 2"));
+
+        let false_positives = (0..1000)
+            .filter(|i| bloom.insert(&format!("select {}", i + 3)))
+            .count();
+        assert!(false_positives < 10, "{}", false_positives);
+
+        bloom.clear();
+        assert!(!bloom.insert("select 1"));
+    }
+}
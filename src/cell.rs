@@ -0,0 +1,186 @@
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::ops::Deref;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::{Number, Value};
+
+/// A value in a snapshot table. It reads as its text, with NULL as empty, for filtering and
+/// printing, but remembers whether it was NULL, or a number, so it's written out as one.
+#[derive(Clone, Debug, Default)]
+pub enum Cell {
+    #[default]
+    Null,
+    Text(String),
+    /// As text, e.g. `42` or `1.500`.
+    Number(String),
+}
+
+impl Cell {
+    pub fn number(n: impl ToString) -> Cell {
+        Cell::Number(n.to_string())
+    }
+
+    pub fn as_str(&self) -> &str {
+        self
+    }
+
+    pub fn is_null(&self) -> bool {
+        matches!(self, Cell::Null)
+    }
+
+    /// A string, a number, or null; a number too big or odd for JSON stays a string.
+    pub fn to_json(&self) -> Value {
+        match self {
+            Cell::Null => Value::Null,
+            Cell::Text(s) => Value::String(s.to_string()),
+            Cell::Number(s) => {
+                if let Ok(n) = s.parse::<i64>() {
+                    Value::from(n)
+                } else if let Some(n) = s.parse::<f64>().ok().and_then(Number::from_f64) {
+                    Value::Number(n)
+                } else {
+                    Value::String(s.to_string())
+                }
+            }
+        }
+    }
+
+    pub fn from_json(value: Value) -> Cell {
+        match value {
+            Value::Null => Cell::Null,
+            Value::String(s) => Cell::Text(s),
+            Value::Number(n) => Cell::Number(n.to_string()),
+            other => Cell::Text(other.to_string()),
+        }
+    }
+}
+
+impl Deref for Cell {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        match self {
+            Cell::Null => "",
+            Cell::Text(s) | Cell::Number(s) => s,
+        }
+    }
+}
+
+impl fmt::Display for Cell {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // `pad`, not `write_str`, so tables can set a width
+        f.pad(self)
+    }
+}
+
+/// Cells are the same if they read the same, whether or not they're numbers; NULL is only the
+/// same as NULL.
+impl PartialEq for Cell {
+    fn eq(&self, other: &Cell) -> bool {
+        self.is_null() == other.is_null() && **self == **other
+    }
+}
+
+impl Eq for Cell {}
+
+impl Hash for Cell {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.is_null().hash(state);
+        (**self).hash(state);
+    }
+}
+
+impl PartialEq<str> for Cell {
+    fn eq(&self, other: &str) -> bool {
+        **self == *other
+    }
+}
+
+impl PartialEq<&str> for Cell {
+    fn eq(&self, other: &&str) -> bool {
+        **self == **other
+    }
+}
+
+impl PartialEq<String> for Cell {
+    fn eq(&self, other: &String) -> bool {
+        **self == *other
+    }
+}
+
+impl PartialEq<Cell> for str {
+    fn eq(&self, other: &Cell) -> bool {
+        other == self
+    }
+}
+
+impl PartialEq<Cell> for &str {
+    fn eq(&self, other: &Cell) -> bool {
+        other == self
+    }
+}
+
+impl PartialEq<Cell> for String {
+    fn eq(&self, other: &Cell) -> bool {
+        other == self
+    }
+}
+
+impl From<String> for Cell {
+    fn from(s: String) -> Cell {
+        Cell::Text(s)
+    }
+}
+
+impl From<&str> for Cell {
+    fn from(s: &str) -> Cell {
+        Cell::Text(s.to_string())
+    }
+}
+
+impl From<Option<String>> for Cell {
+    fn from(s: Option<String>) -> Cell {
+        s.map_or(Cell::Null, Cell::Text)
+    }
+}
+
+impl Serialize for Cell {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.to_json().serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Cell {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Cell, D::Error> {
+        Value::deserialize(deserializer).map(Cell::from_json)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::Cell;
+
+    #[test]
+    fn json_round_trip() {
+        let cells = vec![
+            Cell::Null,
+            Cell::from(""),
+            Cell::from("42"),
+            Cell::number(42),
+            Cell::number("1.500"),
+            Cell::number("NaN"),
+        ];
+        let json = serde_json::to_value(&cells).unwrap();
+        assert_eq!(json!([null, "", "42", 42, 1.5, "NaN"]), json);
+
+        let back: Vec<Cell> = serde_json::from_value(json).unwrap();
+        assert!(back[0].is_null());
+        assert_eq!(Cell::from(""), back[1]);
+        assert_ne!(Cell::Null, back[1]);
+        assert_eq!("1.5", &*back[4]);
+        assert_eq!(cells[..3], back[..3]);
+    }
+}
@@ -0,0 +1,21 @@
+//! ANSI terminal colours. Each wrapper resets only what it set, so they nest.
+
+#[derive(Clone, Copy)]
+pub enum Color {
+    Red,
+    Green,
+    Yellow,
+}
+
+pub fn paint(s: &str, color: Color) -> String {
+    let code = match color {
+        Color::Red => 31,
+        Color::Green => 32,
+        Color::Yellow => 33,
+    };
+    format!("\x1b[{}m{}\x1b[39m", code, s)
+}
+
+pub fn underline(s: &str) -> String {
+    format!("\x1b[4m{}\x1b[24m", s)
+}
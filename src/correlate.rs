@@ -0,0 +1,118 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use anyhow::{anyhow, Result};
+
+use crate::cell::Cell;
+use crate::replay::{Item, Reader};
+use crate::{normalize_query, printer};
+
+/// For one host: the normalized queries seen in each time window.
+type Windows = HashMap<i64, HashSet<String>>;
+
+/// Print the queries which were running on more than one host at once, most hosts first.
+pub fn correlate(paths: &[String], window_secs: i64) -> Result<()> {
+    let mut hosts = Vec::with_capacity(paths.len());
+    for path in paths {
+        hosts.push(windows(path, window_secs)?);
+    }
+
+    let mut lines = vec![vec!["hosts".into(), "windows".into(), "query".into()]];
+    for (hosts, windows, query) in simultaneous(&hosts) {
+        lines.push(vec![
+            Cell::number(hosts),
+            Cell::number(windows),
+            query.chars().take(100).collect::<String>().into(),
+        ]);
+    }
+    print!("{}", printer::render(&lines, &mut [0; 3]));
+    Ok(())
+}
+
+fn windows(path: &str, window_secs: i64) -> Result<Windows> {
+    let mut windows = Windows::new();
+    for item in Reader::open(path)? {
+        let Item::Snapshot {
+            when: Some(when),
+            lines,
+        } = item?
+        else {
+            continue;
+        };
+        let Some((headers, rows)) = lines.split_first() else {
+            continue;
+        };
+        let query_col = headers
+            .iter()
+            .position(|header| header == "query")
+            .ok_or_else(|| anyhow!("no query column in {:?}", path))?;
+        let window = windows
+            .entry(when.timestamp().div_euclid(window_secs))
+            .or_default();
+        for row in rows {
+            window.insert(normalize_query(&row[query_col]));
+        }
+    }
+    Ok(windows)
+}
+
+/// `(hosts, windows, query)`: the most hosts which ran the query in the same window, and in
+/// how many windows that many did; only for queries seen on more than one host at once.
+fn simultaneous(hosts: &[Windows]) -> Vec<(usize, usize, String)> {
+    let mut counts: HashMap<(i64, &str), usize> = HashMap::new();
+    for windows in hosts {
+        for (&window, queries) in windows {
+            for query in queries {
+                *counts.entry((window, query.as_str())).or_default() += 1;
+            }
+        }
+    }
+
+    let mut best: BTreeMap<&str, (usize, usize)> = BTreeMap::new();
+    for ((_, query), hosts) in counts {
+        if hosts < 2 {
+            continue;
+        }
+        let entry = best.entry(query).or_default();
+        if hosts > entry.0 {
+            *entry = (hosts, 1);
+        } else if hosts == entry.0 {
+            entry.1 += 1;
+        }
+    }
+
+    let mut report: Vec<_> = best
+        .into_iter()
+        .map(|(query, (hosts, windows))| (hosts, windows, query.to_string()))
+        .collect();
+    report.sort_by(|a, b| b.0.cmp(&a.0).then(b.1.cmp(&a.1)).then(a.2.cmp(&b.2)));
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{simultaneous, Windows};
+
+    fn host(windows: &[(i64, &[&str])]) -> Windows {
+        windows
+            .iter()
+            .map(|(window, queries)| (*window, queries.iter().map(|q| q.to_string()).collect()))
+            .collect()
+    }
+
+    #[test]
+    fn counts_hosts_per_window() {
+        let hosts = [
+            host(&[(1, &["a", "b"]), (2, &["a"])]),
+            host(&[(1, &["a"]), (2, &["a", "c"])]),
+            host(&[(1, &["b"]), (2, &["a", "c"]), (3, &["d"])]),
+        ];
+        assert_eq!(
+            vec![
+                (3, 1, "a".to_string()),
+                (2, 1, "b".to_string()),
+                (2, 1, "c".to_string()),
+            ],
+            simultaneous(&hosts)
+        );
+    }
+}
@@ -0,0 +1,135 @@
+use std::collections::{HashMap, HashSet};
+
+use anyhow::{anyhow, Result};
+
+use crate::cell::Cell;
+
+/// Remembers the last row seen for one entity (e.g. a database), and turns the
+/// cumulative counters in the next row into the difference since then.
+pub struct DeltaTracker {
+    prev: Option<Vec<Cell>>,
+    counter_cols: Vec<usize>,
+}
+
+impl DeltaTracker {
+    pub fn new(counter_cols: Vec<usize>) -> DeltaTracker {
+        DeltaTracker {
+            prev: None,
+            counter_cols,
+        }
+    }
+
+    /// Returns `None` the first time an entity is seen, as there's nothing to subtract.
+    pub fn update(&mut self, row: Vec<Cell>) -> Option<Vec<Cell>> {
+        let prev = self.prev.replace(row.clone())?;
+        let mut delta = row;
+        for &col in &self.counter_cols {
+            delta[col] = difference(&prev[col], &delta[col]);
+        }
+        Some(delta)
+    }
+}
+
+/// Replace the counters in a snapshot with their deltas, matching up rows by `key_columns`.
+/// Entities which aren't in the snapshot are forgotten, e.g. a dropped table, or a statement
+/// evicted from `pg_stat_statements`, so that one reappearing starts again.
+pub fn apply(
+    trackers: &mut HashMap<Vec<String>, DeltaTracker>,
+    lines: Vec<Vec<Cell>>,
+    key_columns: &[String],
+    counter_columns: &[String],
+) -> Result<Vec<Vec<Cell>>> {
+    let mut lines = lines.into_iter();
+    let headers = lines.next().expect("header row");
+    let key_cols = indices(&headers, key_columns)?;
+    let counter_cols = indices(&headers, counter_columns)?;
+
+    let mut output = Vec::with_capacity(trackers.len() + 1);
+    output.push(headers);
+
+    let mut seen = HashSet::with_capacity(trackers.len());
+    for row in lines {
+        let key: Vec<String> = key_cols.iter().map(|&i| row[i].to_string()).collect();
+        let tracker = trackers
+            .entry(key.clone())
+            .or_insert_with(|| DeltaTracker::new(counter_cols.clone()));
+        if let Some(delta) = tracker.update(row) {
+            output.push(delta);
+        }
+        seen.insert(key);
+    }
+    trackers.retain(|key, _| seen.contains(key));
+
+    Ok(output)
+}
+
+fn indices(headers: &[Cell], names: &[String]) -> Result<Vec<usize>> {
+    names
+        .iter()
+        .map(|name| {
+            headers
+                .iter()
+                .position(|header| header == name)
+                .ok_or_else(|| anyhow!("no column named {:?}", name))
+        })
+        .collect()
+}
+
+fn difference(prev: &str, now: &str) -> Cell {
+    if let (Ok(prev), Ok(now)) = (prev.parse::<i64>(), now.parse::<i64>()) {
+        // the counter was reset (e.g. pg_stat_reset()), so count from zero
+        return Cell::number(if now < prev { now } else { now - prev });
+    }
+    if let (Ok(prev), Ok(now)) = (prev.parse::<f64>(), now.parse::<f64>()) {
+        return Cell::number(if now < prev { now } else { now - prev });
+    }
+    Cell::Null
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::apply;
+    use crate::printer::table;
+
+    #[test]
+    fn deltas_by_key() {
+        let keys = ["datname".to_string()];
+        let counters = ["xact_commit".to_string()];
+        let mut trackers = HashMap::new();
+
+        let first = table(&[&["datname", "xact_commit"], &["a", "10"], &["b", "5"]]);
+        assert_eq!(
+            table(&[&["datname", "xact_commit"]]),
+            apply(&mut trackers, first, &keys, &counters).unwrap()
+        );
+
+        let second = table(&[
+            &["datname", "xact_commit"],
+            &["b", "7"],
+            &["a", "3"],
+            &["c", "1"],
+        ]);
+        assert_eq!(
+            table(&[&["datname", "xact_commit"], &["b", "2"], &["a", "3"]]),
+            apply(&mut trackers, second, &keys, &counters).unwrap()
+        );
+
+        // `a` is gone, so is new again when it comes back
+        let third = table(&[&["datname", "xact_commit"], &["b", "8"], &["c", "4"]]);
+        apply(&mut trackers, third, &keys, &counters).unwrap();
+        assert_eq!(2, trackers.len());
+        let fourth = table(&[&["datname", "xact_commit"], &["a", "20"], &["c", "6"]]);
+        assert_eq!(
+            table(&[&["datname", "xact_commit"], &["c", "2"]]),
+            apply(&mut trackers, fourth, &keys, &counters).unwrap()
+        );
+    }
+
+    #[test]
+    fn missing_column() {
+        let lines = table(&[&["datname"]]);
+        assert!(apply(&mut HashMap::new(), lines, &[], &["nope".to_string()]).is_err());
+    }
+}
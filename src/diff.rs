@@ -0,0 +1,129 @@
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Result};
+use chrono::SecondsFormat;
+
+use crate::cell::Cell;
+use crate::color::{self, Color};
+use crate::printer::{self, ColumnWidths, VOLATILE};
+use crate::replay::{Item, Reader};
+
+/// Print how each snapshot in an activity file differs from the one before, by `pid`: new rows
+/// (`+`, green), gone rows (`-`, red), and changed rows (`~`, yellow, with what changed
+/// underlined).
+pub fn diff(path: &str, use_color: bool, widths: &ColumnWidths) -> Result<()> {
+    let mut prev: Option<Vec<Vec<Cell>>> = None;
+    for item in Reader::open(path)? {
+        let Item::Snapshot { when, lines } = item? else {
+            continue;
+        };
+        let Some(headers) = lines.first() else {
+            continue;
+        };
+        let pid_col = headers
+            .iter()
+            .position(|header| header == "pid")
+            .ok_or_else(|| anyhow!("no pid column in {:?}", path))?;
+
+        let diff = printer::diff_by_pid(prev.as_deref(), &lines, pid_col);
+        if diff.len() > 1 {
+            let when = when
+                .map(|when| when.to_rfc3339_opts(SecondsFormat::Micros, true))
+                .unwrap_or_default();
+            println!("# snapshot {}", when);
+            let changed = changed_cells(prev.as_deref(), &diff, pid_col);
+            print!("{}", render(&diff, &changed, use_color, widths));
+        }
+        prev = Some(lines);
+    }
+    Ok(())
+}
+
+/// For each cell in a `diff_by_pid` table, whether it differs from the previous snapshot.
+fn changed_cells(prev: Option<&[Vec<Cell>]>, diff: &[Vec<Cell>], pid_col: usize) -> Vec<Vec<bool>> {
+    let headers = &diff[0];
+    let before: HashMap<&str, &Vec<Cell>> = prev
+        .map(|prev| &prev[1..])
+        .unwrap_or_default()
+        .iter()
+        .map(|row| (row[pid_col].as_str(), row))
+        .collect();
+
+    diff.iter()
+        .enumerate()
+        .map(|(i, row)| {
+            let old = before.get(row[pid_col + 1].as_str());
+            row.iter()
+                .enumerate()
+                .map(|(col, value)| match old {
+                    Some(old) if i > 0 && row[0] == "~" && col > 0 => {
+                        !VOLATILE.contains(&headers[col].as_str()) && old[col - 1] != *value
+                    }
+                    _ => false,
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// `printer::render`, colouring rows by their `diff` marker.
+fn render(
+    lines: &[Vec<Cell>],
+    changed: &[Vec<bool>],
+    use_color: bool,
+    widths: &ColumnWidths,
+) -> String {
+    // the snapshot's columns start after the marker
+    let widths = ColumnWidths {
+        mins: std::iter::once(0)
+            .chain(widths.mins.iter().copied())
+            .collect(),
+        max: widths.max,
+    };
+    let mut mins = vec![0; lines[0].len()];
+    printer::render_styled(lines, &mut mins, &widths, |row, col, cell| {
+        if !use_color || cell.is_empty() {
+            return cell.to_string();
+        }
+        let cell = match changed[row][col] {
+            true => color::underline(cell),
+            false => cell.to_string(),
+        };
+        match lines[row][0].as_str() {
+            "+" => color::paint(&cell, Color::Green),
+            "-" => color::paint(&cell, Color::Red),
+            "~" => color::paint(&cell, Color::Yellow),
+            _ => cell,
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{changed_cells, render};
+    use crate::printer::{diff_by_pid, table, ColumnWidths};
+
+    #[test]
+    fn colours_rows() {
+        let prev = table(&[&["pid", "state"], &["1", "active"], &["2", "active"]]);
+        let now = table(&[
+            &["pid", "state"],
+            &["1", "idle in transaction"],
+            &["3", "active"],
+        ]);
+        let diff = diff_by_pid(Some(&prev), &now, 0);
+        let changed = changed_cells(Some(&prev), &diff, 0);
+        assert_eq!(vec![false, false, true], changed[1]);
+
+        assert_eq!(
+            concat!(
+                "diff   pid   state\n",
+                "\x1b[33m~\x1b[39m      \x1b[33m1\x1b[39m     ",
+                "\x1b[33m\x1b[4midle in transaction\x1b[24m\x1b[39m\n",
+                "\x1b[32m+\x1b[39m      \x1b[32m3\x1b[39m     \x1b[32mactive\x1b[39m\n",
+                "\x1b[31m-\x1b[39m      \x1b[31m2\x1b[39m     \x1b[31mactive\x1b[39m\n",
+            ),
+            render(&diff, &changed, true, &ColumnWidths::default())
+        );
+    }
+}
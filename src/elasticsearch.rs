@@ -0,0 +1,94 @@
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde_json::{json, Value};
+
+use crate::cell::Cell;
+use crate::writer;
+
+/// Where to index each activity snapshot, with the `_bulk` API.
+pub struct Target {
+    pub url: String,
+    pub index: String,
+}
+
+impl Target {
+    /// Index each row as a document, with an id of `<snapshot time>-<pid>`, so indexing the same
+    /// snapshot again replaces its documents rather than duplicating them.
+    pub fn index(&self, when: DateTime<Utc>, lines: &[Vec<Cell>]) -> Result<()> {
+        if lines.len() < 2 {
+            return Ok(());
+        }
+        let url = format!("{}/_bulk", self.url.trim_end_matches('/'));
+        let response = ureq::post(&url)
+            .timeout(Duration::from_secs(5))
+            .set("Content-Type", "application/x-ndjson")
+            .send_string(&bulk(&self.index, when, lines)?)
+            .with_context(|| anyhow!("indexing into {:?}", self.index))?
+            .into_string()?;
+        let response: Value = serde_json::from_str(&response)
+            .with_context(|| anyhow!("parsing the response from elasticsearch"))?;
+
+        // the request succeeds even if every document fails
+        if response["errors"] == json!(true) {
+            let items = response["items"].as_array().map(Vec::as_slice);
+            let first = items
+                .unwrap_or_default()
+                .iter()
+                .find_map(|item| item["index"].get("error"));
+            bail!(
+                "elasticsearch rejected documents, e.g.: {}",
+                first.unwrap_or(&Value::Null)
+            );
+        }
+        Ok(())
+    }
+}
+
+/// The `_bulk` request body: an action line, then the document, for each row.
+fn bulk(index: &str, when: DateTime<Utc>, lines: &[Vec<Cell>]) -> Result<String> {
+    let pid = lines[0].iter().position(|header| header == "pid");
+    let ts = when.to_rfc3339_opts(SecondsFormat::Micros, true);
+
+    let mut body = String::with_capacity(lines.len() * 1000);
+    for (row, record) in lines[1..].iter().zip(writer::records(lines)) {
+        // a PSD_QUERY_FILE might not have a pid, so elasticsearch will have to pick the id
+        let action = match pid {
+            Some(pid) => {
+                json!({ "index": { "_index": index, "_id": format!("{}-{}", ts, row[pid]) } })
+            }
+            None => json!({ "index": { "_index": index } }),
+        };
+        body.push_str(&serde_json::to_string(&action)?);
+        body.push('\n');
+        body.push_str(&serde_json::to_string(&record)?);
+        body.push('\n');
+    }
+    Ok(body)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, Utc};
+
+    use super::bulk;
+    use crate::printer::table;
+
+    #[test]
+    fn bulk_body() {
+        let lines = table(&[&["pid", "state"], &["1", "active"], &["2", "idle"]]);
+        let when = DateTime::parse_from_rfc3339("2024-01-02T03:04:05.5Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        assert_eq!(
+            concat!(
+                "{\"index\":{\"_index\":\"psd\",\"_id\":\"2024-01-02T03:04:05.500000Z-1\"}}\n",
+                "{\"pid\":\"1\",\"state\":\"active\"}\n",
+                "{\"index\":{\"_index\":\"psd\",\"_id\":\"2024-01-02T03:04:05.500000Z-2\"}}\n",
+                "{\"pid\":\"2\",\"state\":\"idle\"}\n",
+            ),
+            bulk("psd", when, &lines).unwrap()
+        );
+    }
+}
@@ -0,0 +1,149 @@
+use std::collections::HashSet;
+use std::time::Duration;
+
+use anyhow::Result;
+use bunyarrs::{vars_dbg, Bunyarr};
+use chrono::{DateTime, Utc};
+use serde_json::json;
+
+use crate::cell::Cell;
+use crate::printer::ColumnIndex;
+use crate::writer::SnapshotWriter;
+use crate::Fetcher;
+
+/// Statements `EXPLAIN` accepts, by their first word; anything else isn't worth asking about.
+const EXPLAINABLE: &[&str] = &[
+    "select", "with", "insert", "update", "delete", "values", "table",
+];
+
+/// Writes the plan of each query which has been running for longer than `long_query`, once per
+/// run of it, as a snapshot of `pid, datname, query_start, query_age_secs, query, plan` rows.
+pub struct ExplainCollector {
+    pub output: SnapshotWriter,
+    long_query: Duration,
+    /// `(pid, query_start)` of each query already explained, which were still running at the
+    /// last poll.
+    explained: HashSet<(String, String)>,
+    logger: Bunyarr,
+}
+
+impl ExplainCollector {
+    pub fn new(output: SnapshotWriter, long_query: Duration) -> ExplainCollector {
+        ExplainCollector {
+            output,
+            long_query,
+            explained: HashSet::new(),
+            logger: Bunyarr::with_name("explain"),
+        }
+    }
+
+    /// Explain the long queries in an activity snapshot, after its age columns have been added.
+    /// A query which can't be explained, e.g. as it has parameters, is logged and skipped.
+    pub fn collect(
+        &mut self,
+        conn: &mut dyn Fetcher,
+        when: Option<DateTime<Utc>>,
+        lines: &[Vec<Cell>],
+    ) -> Result<()> {
+        let columns = ColumnIndex::build(&lines[0]);
+        let pid = columns.get("pid")?;
+        let datname = columns.get("datname")?;
+        let query_start = columns.get("query_start")?;
+        let query_age = columns.get("query_age_secs")?;
+        let query = columns.get("query")?;
+
+        let mut out = vec![[
+            "pid",
+            "datname",
+            "query_start",
+            "query_age_secs",
+            "query",
+            "plan",
+        ]
+        .iter()
+        .map(|&header| Cell::from(header))
+        .collect::<Vec<_>>()];
+
+        let mut still_running = HashSet::with_capacity(self.explained.len());
+        for row in self.long_queries(lines, &columns)? {
+            let key = (row[pid].to_string(), row[query_start].to_string());
+            let seen = self.explained.contains(&key);
+            still_running.insert(key);
+            if seen {
+                continue;
+            }
+
+            let plan = match conn.explain(&row[datname], &row[query]) {
+                // some other database's query, which we can't plan from here
+                Ok(None) => continue,
+                Ok(Some(plan)) => plan,
+                Err(err) => {
+                    let backend_pid = &row[pid];
+                    self.logger
+                        .warn(vars_dbg! { err, backend_pid }, "explaining query failed");
+                    continue;
+                }
+            };
+            out.push(vec![
+                row[pid].clone(),
+                row[datname].clone(),
+                row[query_start].clone(),
+                row[query_age].clone(),
+                row[query].clone(),
+                plan.to_string().into(),
+            ]);
+        }
+        self.explained = still_running;
+
+        if out.len() > 1 {
+            self.output.write_snapshot(when, &out)?;
+        }
+        Ok(())
+    }
+
+    /// The active rows which have been running for too long, and look explainable.
+    fn long_queries<'a>(
+        &self,
+        lines: &'a [Vec<Cell>],
+        columns: &ColumnIndex,
+    ) -> Result<Vec<&'a Vec<Cell>>> {
+        let state = columns.get("state")?;
+        let query_age = columns.get("query_age_secs")?;
+        let query = columns.get("query")?;
+        let limit = self.long_query.as_secs_f64();
+        Ok(lines[1..]
+            .iter()
+            .filter(|row| row[state] == "active")
+            .filter(|row| row[query_age].parse::<f64>().is_ok_and(|age| age > limit))
+            .filter(|row| explainable(&row[query]))
+            .collect())
+    }
+}
+
+fn explainable(query: &str) -> bool {
+    let first = query
+        .trim_start_matches(|c: char| c.is_whitespace() || c == '(')
+        .split(|c: char| !c.is_ascii_alphabetic())
+        .next()
+        .unwrap_or_default();
+    EXPLAINABLE
+        .iter()
+        .any(|word| word.eq_ignore_ascii_case(first))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::explainable;
+
+    #[test]
+    fn explainable_statements() {
+        assert!(explainable("select 1"));
+        assert!(explainable("  (SELECT 1) union (select 2)"));
+        assert!(explainable("with x as (select 1) select * from x"));
+        assert!(explainable("UPDATE t set x = 1"));
+        assert!(!explainable("vacuum t"));
+        assert!(!explainable("explain select 1"));
+        assert!(!explainable("selection"));
+        assert!(!explainable(""));
+    }
+}
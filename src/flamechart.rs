@@ -0,0 +1,88 @@
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+
+use crate::cell::Cell;
+use crate::normalize_query;
+use crate::replay::{Item, Reader};
+
+const FRAMES: [&str; 4] = ["query", "state", "wait_event_type", "wait_event"];
+
+/// Print the rows in a file as folded stacks, `query;state;wait_event_type;wait_event count`,
+/// for feeding to `flamegraph.pl`.
+pub fn flamechart(path: &str) -> Result<()> {
+    let mut stacks = BTreeMap::new();
+    for item in Reader::open(path)? {
+        if let Item::Snapshot { lines, .. } = item? {
+            fold(&mut stacks, &lines);
+        }
+    }
+    for (stack, count) in stacks {
+        println!("{} {}", stack, count);
+    }
+    Ok(())
+}
+
+fn fold(stacks: &mut BTreeMap<String, u64>, lines: &[Vec<Cell>]) {
+    let Some((headers, rows)) = lines.split_first() else {
+        return;
+    };
+    let cols: Option<Vec<usize>> = FRAMES
+        .iter()
+        .map(|name| headers.iter().position(|header| header == name))
+        .collect();
+    let Some(cols) = cols else {
+        return;
+    };
+
+    for row in rows {
+        let stack = cols
+            .iter()
+            .enumerate()
+            .map(|(i, &col)| {
+                let frame = if 0 == i {
+                    normalize_query(&row[col])
+                } else {
+                    row[col].to_string()
+                };
+                // `;` separates frames, and an empty frame confuses flamegraph.pl
+                match frame.replace(';', ",").trim() {
+                    "" => "-".to_string(),
+                    frame => frame.to_string(),
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(";");
+        *stacks.entry(stack).or_default() += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::fold;
+    use crate::printer::table;
+
+    #[test]
+    fn folds_rows() {
+        let lines = table(&[
+            &["pid", "query", "state", "wait_event_type", "wait_event"][..],
+            &["1", "select 1; select 2", "active", "", ""],
+            &["2", "select 3; select 4", "active", "", ""],
+            &["3", "select pg_sleep(10)", "active", "Timeout", "PgSleep"],
+        ]);
+
+        let mut stacks = BTreeMap::new();
+        fold(&mut stacks, &lines);
+        fold(&mut stacks, &lines[..2]);
+
+        assert_eq!(
+            vec![
+                ("select ?, select ?;active;-;-".to_string(), 3),
+                ("select pg_sleep(?);active;Timeout;PgSleep".to_string(), 1),
+            ],
+            stacks.into_iter().collect::<Vec<_>>()
+        );
+    }
+}
@@ -0,0 +1,103 @@
+use anyhow::Result;
+use chrono::{DateTime, Datelike, Timelike, Utc};
+
+use crate::cell::Cell;
+use crate::replay::{Item, Reader};
+
+const DAYS: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+
+/// Darker is busier; a space is no activity at all.
+const SHADES: &[u8] = b" .:-=+*#%@";
+
+/// Active rows seen, by (day of week, hour of day) of their `query_start`, in UTC.
+type Counts = [[u64; 24]; 7];
+
+/// Print a day of week by hour of day map of when the active queries in a file started.
+pub fn heatmap(path: &str) -> Result<()> {
+    let mut counts = [[0; 24]; 7];
+    for item in Reader::open(path)? {
+        if let Item::Snapshot { lines, .. } = item? {
+            count(&mut counts, &lines);
+        }
+    }
+    print!("{}", render(&counts));
+    Ok(())
+}
+
+fn count(counts: &mut Counts, lines: &[Vec<Cell>]) {
+    let Some((headers, rows)) = lines.split_first() else {
+        return;
+    };
+    let find = |name: &str| headers.iter().position(|header| header == name);
+    let (Some(state_col), Some(start_col)) = (find("state"), find("query_start")) else {
+        return;
+    };
+
+    for row in rows {
+        if row[state_col] != "active" {
+            continue;
+        }
+        let Ok(start) = DateTime::parse_from_rfc3339(&row[start_col]) else {
+            continue;
+        };
+        let start = start.with_timezone(&Utc);
+        counts[start.weekday().num_days_from_monday() as usize][start.hour() as usize] += 1;
+    }
+}
+
+fn render(counts: &Counts) -> String {
+    let max = counts.iter().flatten().copied().max().unwrap_or_default();
+
+    let mut buf = String::with_capacity(8 * 80);
+    buf.push_str("UTC");
+    for hour in 0..24 {
+        buf.push_str(&format!(" {:02}", hour));
+    }
+    buf.push('\n');
+
+    for (day, hours) in DAYS.iter().zip(counts) {
+        buf.push_str(day);
+        for &count in hours {
+            // rounding up, so anything seen at all gets at least the lightest mark
+            let levels = SHADES.len() as u64 - 1;
+            let shade = SHADES[((count * levels).div_ceil(max.max(1))) as usize];
+            buf.push_str("  ");
+            buf.push(shade as char);
+        }
+        buf.push('\n');
+    }
+
+    buf.push_str(&format!("peak: {} active rows in an hour\n", max));
+    buf
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{count, render};
+    use crate::printer::table;
+
+    #[test]
+    fn bins_active_rows() {
+        let lines = table(&[
+            &["state", "query_start"][..],
+            // a Monday
+            &["active", "2024-01-15T06:30:00.000000Z"],
+            &["active", "2024-01-15T06:59:59.000000Z"],
+            &["idle in transaction", "2024-01-15T06:30:00.000000Z"],
+            // a Sunday
+            &["active", "2024-01-21T23:00:00.000000Z"],
+            &["active", ""],
+        ]);
+
+        let mut counts = [[0; 24]; 7];
+        count(&mut counts, &lines);
+        assert_eq!(2, counts[0][6]);
+        assert_eq!(1, counts[6][23]);
+        assert_eq!(3, counts.iter().flatten().sum::<u64>());
+
+        let rendered = render(&counts);
+        let monday = rendered.lines().nth(1).unwrap();
+        assert!(monday.starts_with("Mon  "));
+        assert_eq!(Some('@'), monday.chars().nth(3 + 6 * 3 + 2));
+    }
+}
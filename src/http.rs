@@ -0,0 +1,160 @@
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Context, Result};
+use bunyarrs::{vars, vars_dbg, Bunyarr};
+use serde_json::json;
+
+use crate::cell::Cell;
+use crate::{pushgateway, writer};
+
+/// The most recent activity snapshot, header row first; empty until the first poll.
+pub type Latest = Arc<RwLock<Vec<Vec<Cell>>>>;
+
+/// Listen on `addr`, serving whatever is in the returned `Latest` in the background:
+/// `/snapshot` as JSON, `/metrics` for Prometheus, and `/health`.
+pub fn start(addr: &str) -> Result<Latest> {
+    let listener = TcpListener::bind(addr).with_context(|| anyhow!("listening on {:?}", addr))?;
+    serve(listener)
+}
+
+fn serve(listener: TcpListener) -> Result<Latest> {
+    let logger = Bunyarr::with_name("http");
+    let address = listener.local_addr()?.to_string();
+    logger.info(vars! { address }, "serving http");
+
+    let latest = Latest::default();
+    let shared = Arc::clone(&latest);
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let shared = Arc::clone(&shared);
+            let Ok(stream) = stream else {
+                continue;
+            };
+            // reading the request could take a while, so don't hold up the next client
+            std::thread::spawn(move || {
+                if let Err(err) = respond(stream, &shared) {
+                    Bunyarr::with_name("http").info(vars_dbg! { err }, "http request failed");
+                }
+            });
+        }
+    });
+    Ok(latest)
+}
+
+fn respond(stream: TcpStream, latest: &RwLock<Vec<Vec<Cell>>>) -> Result<()> {
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+    stream.set_write_timeout(Some(Duration::from_secs(5)))?;
+    let mut reader = BufReader::new(stream);
+
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    // the headers don't matter to us, but must be read before we reply
+    let mut request_bytes = request_line.len();
+    loop {
+        let mut line = String::new();
+        request_bytes += reader.read_line(&mut line)?;
+        if line.is_empty() || request_bytes > 16 * 1024 {
+            bail!("incomplete request");
+        }
+        if line.trim_end().is_empty() {
+            break;
+        }
+    }
+
+    let (status, content_type, body) = {
+        let latest = latest.read().expect("latest snapshot lock");
+        route(&request_line, &latest)?
+    };
+    reader.into_inner().write_all(
+        format!(
+            concat!(
+                "HTTP/1.1 {}\r\n",
+                "Content-Type: {}\r\n",
+                "Content-Length: {}\r\n",
+                "Connection: close\r\n\r\n{}"
+            ),
+            status,
+            content_type,
+            body.len(),
+            body
+        )
+        .as_bytes(),
+    )?;
+    Ok(())
+}
+
+/// The status, content type and body for a request line, e.g. `GET /health HTTP/1.1`.
+fn route(request_line: &str, latest: &[Vec<Cell>]) -> Result<(&'static str, &'static str, String)> {
+    let mut parts = request_line.split_whitespace();
+    let (method, path) = (parts.next(), parts.next());
+    // ignore any query string
+    let path = path.map(|path| path.split('?').next().unwrap_or_default());
+    const TEXT: &str = "text/plain; charset=utf-8";
+    Ok(match (method, path) {
+        (Some("GET"), Some("/health")) => ("200 OK", TEXT, "ok\n".to_string()),
+        (Some("GET"), Some("/snapshot" | "/metrics")) if latest.is_empty() => (
+            "503 Service Unavailable",
+            TEXT,
+            "no snapshot yet\n".to_string(),
+        ),
+        (Some("GET"), Some("/snapshot")) => (
+            "200 OK",
+            "application/json",
+            serde_json::to_string(&writer::records(latest))?,
+        ),
+        (Some("GET"), Some("/metrics")) => (
+            "200 OK",
+            "text/plain; version=0.0.4",
+            pushgateway::metrics(latest),
+        ),
+        (Some("GET"), _) => ("404 Not Found", TEXT, "not found\n".to_string()),
+        _ => ("405 Method Not Allowed", TEXT, "only GET\n".to_string()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+    use std::net::{TcpListener, TcpStream};
+
+    use super::serve;
+    use crate::printer::table;
+
+    fn get(addr: std::net::SocketAddr, path: &str) -> String {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream
+            .write_all(format!("GET {} HTTP/1.1\r\nHost: x\r\n\r\n", path).as_bytes())
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    #[test]
+    fn serves_the_latest() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let latest = serve(listener).unwrap();
+
+        assert!(get(addr, "/health").ends_with("\r\n\r\nok\n"));
+        assert!(get(addr, "/snapshot").starts_with("HTTP/1.1 503 "));
+        assert!(get(addr, "/nope").starts_with("HTTP/1.1 404 "));
+
+        *latest.write().unwrap() = table(&[&["datname", "state", "pid"], &["db", "active", "1"]]);
+        let snapshot = get(addr, "/snapshot?pretty");
+        assert!(
+            snapshot.ends_with("\r\n\r\n[{\"datname\":\"db\",\"state\":\"active\",\"pid\":\"1\"}]"),
+            "{}",
+            snapshot
+        );
+        let metrics = get(addr, "/metrics");
+        assert!(
+            metrics.contains("\npg_active_backends{dbname=\"db\"} 1\n"),
+            "{}",
+            metrics
+        );
+    }
+}
@@ -0,0 +1,236 @@
+use std::time::Duration;
+
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Utc};
+
+use crate::cell::Cell;
+
+/// Columns which identify a row, so are written as tags; everything else is a field.
+const TAG_COLUMNS: &[&str] = &["pid", "datname", "usename"];
+
+/// What's sent to `PSD_INFLUX_URL`, from the activity snapshot.
+const ACTIVITY_TAGS: &[&str] = &[
+    "pid",
+    "datname",
+    "usename",
+    "application_name",
+    "state",
+    "wait_event_type",
+];
+const ACTIVITY_FIELDS: &[&str] = &["query_age_secs", "xact_age_secs", "lock_count"];
+
+/// Columns which are already the line's timestamp.
+const SKIPPED_COLUMNS: &[&str] = &["snapshot_at"];
+
+/// Render a snapshot as InfluxDB line protocol, one line per row. Empty values are left out,
+/// as line protocol has no nulls, as are rows with nothing left to write.
+pub fn snapshot(measurement: &str, when: Option<DateTime<Utc>>, lines: &[Vec<Cell>]) -> String {
+    points(measurement, when, lines, TAG_COLUMNS, None)
+}
+
+/// As `snapshot`, but only the columns an InfluxDB dashboard of activity needs.
+pub fn activity(when: Option<DateTime<Utc>>, lines: &[Vec<Cell>]) -> String {
+    points(
+        "pg_stat_activity",
+        when,
+        lines,
+        ACTIVITY_TAGS,
+        Some(ACTIVITY_FIELDS),
+    )
+}
+
+/// `fields` defaults to every column which isn't a tag.
+fn points(
+    measurement: &str,
+    when: Option<DateTime<Utc>>,
+    lines: &[Vec<Cell>],
+    tag_columns: &[&str],
+    field_columns: Option<&[&str]>,
+) -> String {
+    let (headers, rows) = lines.split_first().expect("header row");
+    let timestamp = when
+        .map(|when| format!(" {}", when.timestamp_nanos()))
+        .unwrap_or_default();
+
+    let floats = float_columns(lines);
+
+    let mut buf = String::with_capacity(rows.len() * 200);
+    for row in rows {
+        let mut tags = String::new();
+        let mut fields = Vec::with_capacity(row.len());
+        for ((header, value), &float) in headers.iter().zip(row).zip(&floats) {
+            if value.is_empty() || SKIPPED_COLUMNS.contains(&header.as_str()) {
+                continue;
+            }
+            if tag_columns.contains(&header.as_str()) {
+                tags.push_str(&format!(",{}={}", escape_key(header), escape_key(value)));
+            } else if field_columns.is_none_or(|fields| fields.contains(&header.as_str())) {
+                if let Some(value) = field_value(value, float) {
+                    fields.push(format!("{}={}", escape_key(header), value));
+                }
+            }
+        }
+        if fields.is_empty() {
+            continue;
+        }
+        buf.push_str(&escape_measurement(measurement));
+        buf.push_str(&tags);
+        buf.push(' ');
+        buf.push_str(&fields.join(","));
+        buf.push_str(&timestamp);
+        buf.push('\n');
+    }
+    buf
+}
+
+/// An InfluxDB v2 bucket, written to over HTTP.
+pub struct Target {
+    pub url: String,
+    pub token: Option<String>,
+    pub org: String,
+    pub bucket: String,
+}
+
+impl Target {
+    pub fn write(&self, points: &str) -> Result<()> {
+        let url = format!("{}/api/v2/write", self.url.trim_end_matches('/'));
+        let mut request = ureq::post(&url)
+            .timeout(Duration::from_secs(5))
+            .query("org", &self.org)
+            .query("bucket", &self.bucket)
+            .query("precision", "ns")
+            .set("Content-Type", "text/plain; charset=utf-8");
+        if let Some(token) = &self.token {
+            request = request.set("Authorization", &format!("Token {}", token));
+        }
+        request
+            .send_string(points)
+            .with_context(|| anyhow!("writing to influx bucket {:?}", self.bucket))?;
+        Ok(())
+    }
+}
+
+/// A field's type has to be the same in every row, so a column of numbers is of floats if any
+/// of them has a fraction or exponent, e.g. a `float8` column of `12` and `12.5`, and of
+/// integers otherwise.
+fn float_columns(lines: &[Vec<Cell>]) -> Vec<bool> {
+    let mut floats = vec![false; lines[0].len()];
+    for row in &lines[1..] {
+        for (cell, float) in row.iter().zip(floats.iter_mut()) {
+            if let Cell::Number(n) = cell {
+                *float |= n.contains(['.', 'e', 'E']);
+            }
+        }
+    }
+    floats
+}
+
+/// Integers get an `i` suffix, so they stay integers, and text is always a string, however
+/// it reads. `None` for numbers line protocol can't write, e.g. `NaN`.
+fn field_value(value: &Cell, float: bool) -> Option<String> {
+    match value {
+        Cell::Number(n) if !n.parse::<f64>().is_ok_and(f64::is_finite) => None,
+        Cell::Number(n) if float => Some(n.to_string()),
+        Cell::Number(n) => Some(format!("{}i", n)),
+        _ => Some(format!(
+            "\"{}\"",
+            value.replace('\\', "\\\\").replace('"', "\\\"")
+        )),
+    }
+}
+
+fn escape_measurement(s: &str) -> String {
+    s.replace(',', "\\,").replace(' ', "\\ ")
+}
+
+/// For tag keys, tag values and field keys.
+fn escape_key(s: &str) -> String {
+    escape_measurement(s).replace('=', "\\=")
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, Utc};
+
+    use super::{activity, snapshot};
+    use crate::cell::Cell;
+    use crate::printer::table;
+
+    #[test]
+    fn line_protocol() {
+        let mut lines = table(&[
+            &[
+                "snapshot_at",
+                "pid",
+                "datname",
+                "usename",
+                "state",
+                "query",
+                "query_age_secs",
+            ][..],
+            &[
+                "x",
+                "123",
+                "mydb",
+                "app",
+                "active",
+                "select \"a b\"",
+                "5.300",
+            ],
+            &["x", "124", "", "", "", "", ""],
+        ]);
+        lines[1][6] = Cell::number("5.300");
+
+        let when = DateTime::parse_from_rfc3339("2024-01-15T06:30:00Z").unwrap();
+        assert_eq!(
+            concat!(
+                r#"pg_stat_activity,pid=123,datname=mydb,usename=app state="active","#,
+                r#"query="select \"a b\"",query_age_secs=5.300 1705300200000000000"#,
+                "\n"
+            ),
+            snapshot("pg_stat_activity", Some(when.with_timezone(&Utc)), &lines)
+        );
+        assert_eq!(
+            concat!(
+                "pg_stat_activity,pid=123,datname=mydb,usename=app,state=active",
+                " query_age_secs=5.300 1705300200000000000\n"
+            ),
+            activity(Some(when.with_timezone(&Utc)), &lines)
+        );
+    }
+
+    #[test]
+    fn field_types_come_from_cells() {
+        let mut lines = table(&[
+            &["pid", "ratio", "lock_count", "application_name"][..],
+            &["1", "", "", "12"],
+            &["2", "", "", ""],
+        ]);
+        // as float8 12 and 12.5 read
+        lines[1][1] = Cell::number(12.0);
+        lines[2][1] = Cell::number(12.5);
+        lines[1][2] = Cell::number(3);
+        lines[2][2] = Cell::number(f64::NAN);
+        assert_eq!(
+            concat!(
+                "m,pid=1 ratio=12,lock_count=3i,application_name=\"12\"\n",
+                "m,pid=2 ratio=12.5\n",
+            ),
+            snapshot("m", None, &lines)
+        );
+    }
+
+    #[test]
+    fn activity_fields_are_typed() {
+        let mut lines = table(&[
+            &["pid", "state", "query_age_secs", "lock_count"][..],
+            &["1", "active", "", ""],
+        ]);
+        lines[1][2] = Cell::number("2.000");
+        lines[1][3] = Cell::number(4);
+        assert_eq!(
+            "pg_stat_activity,pid=1,state=active query_age_secs=2.000,lock_count=4i\n",
+            activity(None, &lines)
+        );
+    }
+}
@@ -0,0 +1,171 @@
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Context, Result};
+use bunyarrs::{vars, vars_dbg, Bunyarr};
+use rdkafka::config::ClientConfig;
+use rdkafka::message::Message;
+use rdkafka::producer::{DeliveryFuture, FutureProducer, FutureRecord, Producer};
+use serde_json::json;
+
+use crate::cell::Cell;
+use crate::writer;
+
+/// How long to wait for the messages still queued when the dump exits.
+const FLUSH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Kafka's own rules for a topic name.
+pub fn check_topic(topic: &str) -> Result<()> {
+    let legal = |c: char| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-');
+    if topic.is_empty() || topic.len() > 249 || !topic.chars().all(legal) {
+        bail!("invalid Kafka topic: {:?}", topic);
+    }
+    Ok(())
+}
+
+/// Produces each row of a snapshot, as a JSON object keyed by its `pid`, to a Kafka topic.
+/// Producing only queues the messages, which librdkafka sends in the background, while a thread
+/// of ours waits on their delivery reports to log the failures; the dump never waits for the
+/// brokers.
+pub struct Publisher {
+    producer: FutureProducer,
+    topic: String,
+    deliveries: mpsc::Sender<DeliveryFuture>,
+    logger: Bunyarr,
+}
+
+impl Publisher {
+    pub fn new(brokers: &str, topic: &str) -> Result<Publisher> {
+        check_topic(topic)?;
+        let producer: FutureProducer = ClientConfig::new()
+            .set("bootstrap.servers", brokers)
+            .create()
+            .with_context(|| anyhow!("creating a Kafka producer for {:?}", brokers))?;
+        let (deliveries, reports) = mpsc::channel();
+        let topic = topic.to_string();
+        {
+            let topic = topic.clone();
+            thread::Builder::new()
+                .name("kafka-deliveries".to_string())
+                .spawn(move || log_failures(&topic, reports))?;
+        }
+        Ok(Publisher {
+            producer,
+            topic,
+            deliveries,
+            logger: Bunyarr::with_name("kafka"),
+        })
+    }
+
+    /// A full queue, e.g. as the brokers have been unreachable for a while, costs the rest of
+    /// the snapshot.
+    pub fn publish(&self, lines: &[Vec<Cell>]) {
+        let pid = lines[0].iter().position(|header| header == "pid");
+        for (row, record) in lines[1..].iter().zip(writer::records(lines)) {
+            let payload = serde_json::to_vec(&record).expect("a record is valid json");
+            let key = pid.map_or("", |pid| row[pid].as_str());
+            let record = FutureRecord::to(&self.topic).key(key).payload(&payload);
+            match self.producer.send_result(record) {
+                // the thread only goes away with us
+                Ok(delivery) => self.deliveries.send(delivery).expect("delivery thread"),
+                Err((err, _)) => {
+                    let topic = &self.topic;
+                    self.logger
+                        .warn(vars_dbg! { err, topic }, "queueing Kafka messages failed");
+                    return;
+                }
+            }
+        }
+    }
+}
+
+impl Drop for Publisher {
+    fn drop(&mut self) {
+        if let Err(err) = self.producer.flush(FLUSH_TIMEOUT) {
+            let topic = &self.topic;
+            self.logger
+                .warn(vars_dbg! { err, topic }, "Kafka messages left undelivered");
+        }
+    }
+}
+
+fn log_failures(topic: &str, reports: mpsc::Receiver<DeliveryFuture>) {
+    let logger = Bunyarr::with_name("kafka");
+    for report in reports {
+        // otherwise delivered, or dropped with the producer
+        if let Ok(Err((err, message))) = futures_executor::block_on(report) {
+            let err = err.to_string();
+            let key = message
+                .key_view::<str>()
+                .and_then(|key| key.ok())
+                .unwrap_or_default()
+                .to_string();
+            logger.warn(
+                vars! { err, topic, key },
+                "delivering a Kafka message failed",
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use rdkafka::consumer::{BaseConsumer, Consumer};
+    use rdkafka::mocking::MockCluster;
+    use rdkafka::{ClientConfig, Message, Offset, TopicPartitionList};
+    use serde_json::{json, Value};
+
+    use super::{check_topic, Publisher};
+    use crate::printer::table;
+
+    #[test]
+    fn topic_names() {
+        assert!(check_topic("pg-stat-activity").is_ok());
+        assert!(check_topic("pg_stat.activity-2").is_ok());
+        assert!(check_topic("").is_err());
+        assert!(check_topic("has space").is_err());
+        assert!(check_topic(&"x".repeat(250)).is_err());
+    }
+
+    #[test]
+    fn a_message_per_row() {
+        let cluster = MockCluster::new(1).unwrap();
+        cluster.create_topic("activity", 1, 1).unwrap();
+        let lines = table(&[&["pid", "state"], &["12", "active"], &["34", ""]]);
+        // dropping it waits for the deliveries
+        drop({
+            let publisher = Publisher::new(&cluster.bootstrap_servers(), "activity").unwrap();
+            publisher.publish(&lines);
+            publisher
+        });
+
+        let consumer: BaseConsumer = ClientConfig::new()
+            .set("bootstrap.servers", cluster.bootstrap_servers())
+            .set("group.id", "test")
+            .create()
+            .unwrap();
+        let mut partitions = TopicPartitionList::new();
+        partitions
+            .add_partition_offset("activity", 0, Offset::Beginning)
+            .unwrap();
+        consumer.assign(&partitions).unwrap();
+
+        let mut received = Vec::new();
+        while received.len() < 2 {
+            let message = consumer.poll(Duration::from_secs(10)).unwrap().unwrap();
+            let key = message.key_view::<str>().unwrap().unwrap().to_string();
+            let payload: Value = serde_json::from_slice(message.payload().unwrap()).unwrap();
+            received.push((key, payload));
+        }
+        assert_eq!(
+            vec![
+                ("12".to_string(), json!({ "pid": "12", "state": "active" })),
+                ("34".to_string(), json!({ "pid": "34", "state": null })),
+            ],
+            received
+        );
+    }
+}
@@ -0,0 +1,92 @@
+use std::io::Write;
+use std::time::Duration;
+
+use anyhow::Result;
+
+use crate::cell::Cell;
+use crate::printer::{self, ColIndices, ColumnIndex};
+use crate::{clean_ws, config_connecting_to, connect, Fetcher};
+
+/// What's shown of each session, when the query has them; the query goes last, as it's long.
+const COLUMNS: &[&str] = &[
+    "pid",
+    "datname",
+    "usename",
+    "application_name",
+    "query_age_secs",
+    "wait_event_type",
+    "wait_event",
+    "query",
+];
+
+/// Poll the database, redrawing its active sessions on the terminal each time, until killed.
+/// Nothing is written to disk; the rest of the activity configuration still applies.
+pub fn live(conn_string: &str, interval: Duration) -> Result<()> {
+    let cfg = config_connecting_to(Some(conn_string.to_string()))?;
+    let mut conn = connect(&cfg)?;
+    // kept between polls, so the columns don't jump around as the rows change
+    let mut widths = Vec::new();
+    loop {
+        let (_, mut lines) = conn.fetch(&cfg, None)?;
+        let columns = ColumnIndex::build(&lines[0]);
+        if let Some(col_indices) = ColIndices::find(&columns) {
+            printer::add_age_columns(&mut lines, &col_indices);
+        }
+        let lines = active(&lines);
+        widths.resize(lines[0].len(), 0);
+        let table = printer::render_capped(&lines, &mut widths, &cfg.column_widths);
+        // clear the screen, and go to the top left
+        print!("\x1b[2J\x1b[H{}", table);
+        std::io::stdout().flush()?;
+        std::thread::sleep(interval);
+    }
+}
+
+/// The active rows, with only the `COLUMNS` the query has, and each query on one line.
+fn active(lines: &[Vec<Cell>]) -> Vec<Vec<Cell>> {
+    let (headers, rows) = lines.split_first().expect("header row");
+    let find = |name: &str| headers.iter().position(|header| header == name);
+    let mut cols: Vec<usize> = COLUMNS.iter().filter_map(|name| find(name)).collect();
+    // a PSD_QUERY_FILE with none of them
+    if cols.is_empty() {
+        cols = (0..headers.len()).collect();
+    }
+    let state = find("state");
+
+    let mut shown = vec![cols.iter().map(|&col| headers[col].clone()).collect()];
+    for row in rows {
+        if state.is_some_and(|state| row[state] != "active") {
+            continue;
+        }
+        shown.push(
+            cols.iter()
+                .map(|&col| match &row[col] {
+                    Cell::Null => Cell::Null,
+                    cell => clean_ws(cell.trim()).into(),
+                })
+                .collect(),
+        );
+    }
+    shown
+}
+
+#[cfg(test)]
+mod tests {
+    use super::active;
+    use crate::printer::table;
+
+    #[test]
+    fn active_only() {
+        assert_eq!(
+            table(&[
+                &["pid", "usename", "query"],
+                &["1", "app", "select 1 from t"],
+            ]),
+            active(&table(&[
+                &["backend_xid", "pid", "state", "usename", "query"],
+                &["", "1", "active", "app", " select 1\n  from t\n"],
+                &["", "2", "idle in transaction", "app", "select 2"],
+            ]))
+        );
+    }
+}
@@ -0,0 +1,168 @@
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use anyhow::{anyhow, Result};
+
+use crate::cell::Cell;
+use crate::normalize_query;
+use crate::printer;
+use crate::replay::{Item, Reader};
+
+/// What we saw waiting for one `(relation, locktype)`.
+#[derive(Default)]
+struct Contention {
+    max_waiters: usize,
+    snapshots: usize,
+    /// How many waiter-snapshots each (normalised) blocking query was responsible for.
+    blockers: HashMap<String, usize>,
+}
+
+/// Print, for each relation and lock type in a `PSD_ENABLE_LOCK_WAITS` file, the most sessions
+/// seen waiting at once, and the query most often blocking them, most contended first.
+pub fn lock_contention(path: &str) -> Result<()> {
+    let mut contention = BTreeMap::new();
+    for item in Reader::open(path)? {
+        if let Item::Snapshot { lines, .. } = item? {
+            add(&mut contention, &lines)?;
+        }
+    }
+    print!("{}", printer::render(&report(contention), &mut [0; 5]));
+    Ok(())
+}
+
+fn add(contention: &mut BTreeMap<(String, String), Contention>, lines: &[Vec<Cell>]) -> Result<()> {
+    let Some((headers, rows)) = lines.split_first() else {
+        return Ok(());
+    };
+    let find = |name: &str| {
+        headers
+            .iter()
+            .position(|header| header == name)
+            .ok_or_else(|| anyhow!("no column named {:?}; is this a lock-waits file?", name))
+    };
+    let pid = find("pid")?;
+    let relation = find("relation")?;
+    let locktype = find("locktype")?;
+    let blocking_query = find("blocking_query")?;
+
+    let mut waiters: BTreeMap<(String, String), BTreeSet<&str>> = BTreeMap::new();
+    for row in rows {
+        let key = (row[relation].to_string(), row[locktype].to_string());
+        waiters.entry(key.clone()).or_default().insert(&row[pid]);
+        // a pid waiting on several sessions is counted against each of them
+        if !row[blocking_query].is_empty() {
+            *contention
+                .entry(key)
+                .or_default()
+                .blockers
+                .entry(normalize_query(&row[blocking_query]))
+                .or_default() += 1;
+        }
+    }
+    for (key, pids) in waiters {
+        let entry = contention.entry(key).or_default();
+        entry.max_waiters = entry.max_waiters.max(pids.len());
+        entry.snapshots += 1;
+    }
+    Ok(())
+}
+
+fn report(contention: BTreeMap<(String, String), Contention>) -> Vec<Vec<Cell>> {
+    let mut contention: Vec<_> = contention.into_iter().collect();
+    // stable within equal counts, as the map was in key order
+    contention.sort_by_key(|(_, seen)| Reverse((seen.max_waiters, seen.snapshots)));
+
+    let mut lines = vec![[
+        "relation",
+        "locktype",
+        "max_waiters",
+        "snapshots",
+        "top_blocking_query",
+    ]
+    .iter()
+    .map(|&header| Cell::from(header))
+    .collect::<Vec<_>>()];
+
+    for ((relation, locktype), seen) in contention {
+        let top = seen
+            .blockers
+            .into_iter()
+            .max_by(|(a_query, a), (b_query, b)| a.cmp(b).then_with(|| b_query.cmp(a_query)))
+            .map(|(query, _)| query)
+            .unwrap_or_default();
+        lines.push(vec![
+            // e.g. transactionid locks have none
+            Some(relation)
+                .filter(|relation| !relation.is_empty())
+                .into(),
+            locktype.into(),
+            Cell::number(seen.max_waiters),
+            Cell::number(seen.snapshots),
+            top.into(),
+        ]);
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::{add, report};
+    use crate::printer::table;
+
+    #[test]
+    fn counts_waiters() {
+        let headers: &[&str] = &[
+            "pid",
+            "locktype",
+            "relation",
+            "blocking_pid",
+            "blocking_query",
+        ];
+        let mut contention = BTreeMap::new();
+        add(
+            &mut contention,
+            &table(&[
+                headers,
+                &["1", "relation", "orders", "9", "lock table orders"],
+                &["2", "relation", "orders", "9", "lock table orders"],
+                &[
+                    "2",
+                    "relation",
+                    "orders",
+                    "1",
+                    "alter table orders add x int",
+                ],
+                &["3", "transactionid", "", "8", "update users set x = 1"],
+            ]),
+        )
+        .unwrap();
+        add(
+            &mut contention,
+            &table(&[
+                headers,
+                &["3", "transactionid", "", "8", "update users set x = 2"],
+                &["4", "transactionid", "", "8", "update users set x = 3"],
+                &["5", "transactionid", "", "", ""],
+            ]),
+        )
+        .unwrap();
+        assert!(add(&mut contention, &table(&[&["pid", "state"]])).is_err());
+
+        assert_eq!(
+            table(&[
+                &[
+                    "relation",
+                    "locktype",
+                    "max_waiters",
+                    "snapshots",
+                    "top_blocking_query"
+                ],
+                &["", "transactionid", "3", "2", "update users set x = ?"],
+                &["orders", "relation", "2", "1", "lock table orders"],
+            ]),
+            report(contention)
+        );
+    }
+}
@@ -0,0 +1,231 @@
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Context, Result};
+use async_nats::connection::State;
+use async_nats::{Client, ConnectOptions, Event, ServerAddr};
+use bunyarrs::{vars, vars_dbg, Bunyarr};
+use serde_json::json;
+use tokio::runtime::Runtime;
+
+use crate::cell::Cell;
+use crate::writer;
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The server, from e.g. `nats://localhost:4222`, or just `localhost`, and the subject.
+pub fn check(url: &str, subject: &str) -> Result<ServerAddr> {
+    if subject.is_empty() || subject.contains(char::is_whitespace) {
+        bail!("invalid NATS subject: {:?}", subject);
+    }
+    url.parse()
+        .with_context(|| anyhow!("expected nats://host[:port], not {:?}", url))
+}
+
+/// Publishes each row of a snapshot, as a JSON object, to a NATS subject. Connects on first
+/// use, and again on the next snapshot if that fails; once connected, the client answers the
+/// server's pings and reconnects by itself, and snapshots taken while it's away are skipped.
+/// A broken connection costs snapshots, not the dump.
+pub struct Publisher {
+    server: ServerAddr,
+    /// `host:port`, for logging without any password in the url.
+    address: String,
+    subject: String,
+    runtime: Runtime,
+    client: Option<Client>,
+    logger: Bunyarr,
+}
+
+impl Publisher {
+    pub fn new(url: &str, subject: &str) -> Result<Publisher> {
+        let server = check(url, subject)?;
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .thread_name("nats")
+            .enable_all()
+            .build()?;
+        Ok(Publisher {
+            address: format!("{}:{}", server.host(), server.port()),
+            server,
+            subject: subject.to_string(),
+            runtime,
+            client: None,
+            logger: Bunyarr::with_name("nats"),
+        })
+    }
+
+    pub fn publish(&mut self, lines: &[Vec<Cell>]) {
+        if let Err(err) = self.try_publish(lines) {
+            let address = &self.address;
+            self.logger
+                .warn(vars_dbg! { err, address }, "publishing to NATS failed");
+        }
+    }
+
+    fn try_publish(&mut self, lines: &[Vec<Cell>]) -> Result<()> {
+        let client = match &self.client {
+            Some(client) => client,
+            None => {
+                let client = self
+                    .runtime
+                    .block_on(connect(self.server.clone(), self.address.clone()))?;
+                self.client.insert(client)
+            }
+        };
+        if client.connection_state() != State::Connected {
+            bail!("not connected, so skipping the snapshot");
+        }
+        self.runtime.block_on(async {
+            tokio::time::timeout(TIMEOUT, async {
+                for record in writer::records(lines) {
+                    let payload = serde_json::to_vec(&record)?;
+                    client.publish(self.subject.clone(), payload.into()).await?;
+                }
+                Ok::<_, anyhow::Error>(())
+            })
+            .await
+            .with_context(|| anyhow!("timed out queueing messages"))?
+        })
+    }
+}
+
+impl Drop for Publisher {
+    fn drop(&mut self) {
+        if let Some(client) = &self.client {
+            let flushed = self
+                .runtime
+                .block_on(async { tokio::time::timeout(TIMEOUT, client.flush()).await });
+            if !matches!(flushed, Ok(Ok(()))) {
+                let address = &self.address;
+                self.logger
+                    .warn(vars! { address }, "NATS messages left unsent");
+            }
+        }
+    }
+}
+
+async fn connect(server: ServerAddr, address: String) -> Result<Client> {
+    let context = anyhow!("connecting to {:?}", address);
+    ConnectOptions::new()
+        .name("pg-stat-dump")
+        .connection_timeout(TIMEOUT)
+        .event_callback(move |event| {
+            let address = address.clone();
+            async move { log_event(&address, event) }
+        })
+        .connect(server)
+        .await
+        .context(context)
+}
+
+/// e.g. the server's `-ERR`s, which don't otherwise reach us.
+fn log_event(address: &str, event: Event) {
+    let logger = Bunyarr::with_name("nats");
+    match event {
+        Event::Connected => logger.info(vars! { address }, "connected to NATS"),
+        Event::Disconnected => logger.warn(vars! { address }, "lost the NATS connection"),
+        Event::ServerError(err) => {
+            let err = err.to_string();
+            logger.warn(vars! { address, err }, "NATS reported an error");
+        }
+        event => {
+            let event = event.to_string();
+            logger.warn(vars! { address, event }, "NATS reported a problem");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::{TcpListener, TcpStream};
+
+    use super::{check, Publisher};
+    use crate::printer::table;
+
+    #[test]
+    fn urls() {
+        assert!(check("nats://localhost", "psd.test").is_ok());
+        assert!(check("10.0.0.1:1234", "psd.test").is_ok());
+        assert!(check("http://localhost", "psd.test").is_err());
+        assert!(check("nats://localhost", "psd test").is_err());
+    }
+
+    #[test]
+    fn publishes_rows_and_answers_pings() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("nats://{}", listener.local_addr().unwrap());
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            stream
+                .write_all(b"INFO {\"server_id\":\"test\",\"max_payload\":1048576}\r\n")
+                .unwrap();
+            let mut reader = BufReader::new(stream);
+            // the client pings whenever it likes, and they're answered here, out of the way
+            let read_line = |reader: &mut BufReader<TcpStream>| loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line != "PING\r\n" {
+                    return line;
+                }
+                reader.get_mut().write_all(b"PONG\r\n").unwrap();
+            };
+            assert!(read_line(&mut reader).starts_with("CONNECT {"));
+
+            let mut received = Vec::new();
+            while received.len() < 2 {
+                let mut line = read_line(&mut reader);
+                let len = line.strip_prefix("PUB psd.test ").unwrap().trim();
+                let mut payload = vec![0; len.parse::<usize>().unwrap() + 2];
+                reader.read_exact(&mut payload).unwrap();
+                line.push_str(&String::from_utf8(payload).unwrap());
+                received.push(line);
+            }
+
+            // the client has to answer, or the server would drop it
+            reader.get_mut().write_all(b"PING\r\n").unwrap();
+            assert_eq!("PONG\r\n", read_line(&mut reader));
+            received
+        });
+
+        let lines = table(&[&["pid", "state"], &["1", "active"], &["2", ""]]);
+        let mut publisher = Publisher::new(&url, "psd.test").unwrap();
+        publisher.publish(&lines);
+        assert!(publisher.client.is_some());
+
+        assert_eq!(
+            vec![
+                "PUB psd.test 28\r\n{\"pid\":\"1\",\"state\":\"active\"}\r\n",
+                "PUB psd.test 24\r\n{\"pid\":\"2\",\"state\":null}\r\n",
+            ],
+            server.join().unwrap()
+        );
+    }
+
+    #[test]
+    fn refused_connections_are_retried() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("nats://{}", listener.local_addr().unwrap());
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            stream
+                .write_all(b"INFO {\"server_id\":\"test\",\"max_payload\":1048576}\r\n")
+                .unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut connect = String::new();
+            reader.read_line(&mut connect).unwrap();
+            reader
+                .get_mut()
+                .write_all(b"-ERR 'Authorization Violation'\r\n")
+                .unwrap();
+            // the client gives up on the connection
+            let mut rest = Vec::new();
+            reader.read_to_end(&mut rest).unwrap();
+        });
+
+        let lines = table(&[&["pid"], &["1"]]);
+        let mut publisher = Publisher::new(&url, "psd.test").unwrap();
+        publisher.publish(&lines);
+        assert!(publisher.client.is_none());
+        server.join().unwrap();
+    }
+}
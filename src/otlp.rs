@@ -0,0 +1,60 @@
+use anyhow::{anyhow, Context, Result};
+use opentelemetry::global::{self, BoxedSpan, BoxedTracer};
+use opentelemetry::trace::{TraceContextExt, Tracer};
+use opentelemetry::KeyValue;
+use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+use opentelemetry_sdk::trace::SdkTracerProvider;
+use opentelemetry_sdk::Resource;
+
+/// Send spans to an OTLP/HTTP endpoint, e.g. `http://localhost:4318/v1/traces`. Until this is
+/// called, `tracer()` hands out spans which go nowhere.
+pub fn init(endpoint: &str) -> Result<SdkTracerProvider> {
+    let exporter = SpanExporter::builder()
+        .with_http()
+        .with_endpoint(endpoint)
+        .build()
+        .with_context(|| anyhow!("configuring otlp exporter for {:?}", endpoint))?;
+    // the batch exporter has its own thread, so exporting doesn't hold up polling
+    let provider = SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(
+            Resource::builder()
+                .with_service_name(env!("CARGO_PKG_NAME"))
+                .build(),
+        )
+        .build();
+    global::set_tracer_provider(provider.clone());
+    Ok(provider)
+}
+
+pub fn tracer() -> BoxedTracer {
+    global::tracer(env!("CARGO_PKG_NAME"))
+}
+
+/// A root span for one poll of one query, with `fetch` and `write` children.
+pub struct Poll<'t> {
+    tracer: &'t BoxedTracer,
+    cx: opentelemetry::Context,
+}
+
+impl Poll<'_> {
+    pub fn start(tracer: &BoxedTracer) -> Poll<'_> {
+        let span = tracer.start("pg_stat_dump.poll");
+        Poll {
+            tracer,
+            cx: opentelemetry::Context::current_with_span(span),
+        }
+    }
+
+    pub fn fetch(&self, sql: &str) -> BoxedSpan {
+        self.tracer
+            .span_builder("pg_stat_dump.fetch")
+            .with_attributes([KeyValue::new("db.statement", sql.to_string())])
+            .start_with_context(self.tracer, &self.cx)
+    }
+
+    pub fn write(&self) -> BoxedSpan {
+        self.tracer
+            .start_with_context("pg_stat_dump.write", &self.cx)
+    }
+}
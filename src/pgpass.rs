@@ -0,0 +1,119 @@
+use std::fs;
+use std::path::PathBuf;
+
+use bunyarrs::{vars, vars_dbg, Bunyarr};
+use postgres::config::Host;
+use serde_json::json;
+
+/// The password for a connection which doesn't have one, from the password file, as libpq
+/// would find it: the first line whose every field matches, or is `*`. Any failure to read the
+/// file is logged, and just means no password.
+pub fn lookup_pgpass(host: &str, port: u16, dbname: &str, user: &str) -> Option<String> {
+    let path = match std::env::var_os("PGPASSFILE") {
+        Some(path) => PathBuf::from(path),
+        None => PathBuf::from(std::env::var_os("HOME")?).join(".pgpass"),
+    };
+    let logger = Bunyarr::with_name("pgpass");
+    let contents = match fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return None,
+        Err(err) => {
+            logger.warn(vars_dbg! { err, path }, "reading password file failed");
+            return None;
+        }
+    };
+
+    // as libpq does, refuse to use a file anyone else can read
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = fs::metadata(&path).ok()?.permissions().mode();
+        if mode & 0o077 != 0 {
+            let path = path.display().to_string();
+            let mode = format!("{:o}", mode & 0o777);
+            logger.warn(
+                vars! { path, mode },
+                "password file is accessible by other users, so is being ignored",
+            );
+            return None;
+        }
+    }
+
+    find(&contents, host, &port.to_string(), dbname, user)
+}
+
+/// `lookup_pgpass`, for the first host of `config`, with libpq's defaults for what it leaves
+/// out; `None` if it has a password already.
+pub fn password_for(config: &postgres::Config) -> Option<String> {
+    if config.get_password().is_some() {
+        return None;
+    }
+    let user = config.get_user()?;
+    // sockets match `localhost`
+    let host = match config.get_hosts().first() {
+        Some(Host::Tcp(host)) => host.as_str(),
+        _ => "localhost",
+    };
+    let port = config.get_ports().first().copied().unwrap_or(5432);
+    let dbname = config.get_dbname().unwrap_or(user);
+    lookup_pgpass(host, port, dbname, user)
+}
+
+fn find(contents: &str, host: &str, port: &str, dbname: &str, user: &str) -> Option<String> {
+    let wanted = [host, port, dbname, user];
+    contents
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .map(fields)
+        .find(|fields| {
+            fields.len() == 5
+                && wanted
+                    .iter()
+                    .zip(fields)
+                    .all(|(wanted, field)| field == "*" || field == wanted)
+        })
+        .map(|mut fields| fields.remove(4))
+}
+
+/// A line split on `:`, with `\:` and `\\` unescaped.
+fn fields(line: &str) -> Vec<String> {
+    let mut fields = vec![String::new()];
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => fields.last_mut().expect("a field").extend(chars.next()),
+            ':' => fields.push(String::new()),
+            c => fields.last_mut().expect("a field").push(c),
+        }
+    }
+    fields
+}
+
+#[cfg(test)]
+mod tests {
+    use super::find;
+
+    #[test]
+    fn first_match_wins() {
+        let contents = concat!(
+            "# host:port:db:user:password\n",
+            "db.example.com:5432:app:alice:first\n",
+            "*:*:*:alice:wild\n",
+            r"db.example.com:5432:app:bob:a\:b\\c",
+            "\n",
+            "short:line\n",
+        );
+        let find = |host, port, dbname, user| find(contents, host, port, dbname, user);
+        assert_eq!(
+            Some("first"),
+            find("db.example.com", "5432", "app", "alice").as_deref()
+        );
+        assert_eq!(Some("wild"), find("other", "6432", "x", "alice").as_deref());
+        assert_eq!(
+            Some(r"a:b\c"),
+            find("db.example.com", "5432", "app", "bob").as_deref()
+        );
+        assert_eq!(None, find("db.example.com", "5433", "app", "bob"));
+        assert_eq!(None, find("short", "5432", "app", "line"));
+    }
+}
@@ -0,0 +1,115 @@
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::{anyhow, bail, Context, Result};
+
+/// The settings of `service`, from the connection service file, as a key=value connection
+/// string. The file is `$PGSERVICEFILE`, or `~/.pg_service.conf`, as for libpq.
+pub fn conn_string_from_service(service: &str) -> Result<String> {
+    let path = match std::env::var_os("PGSERVICEFILE") {
+        Some(path) => PathBuf::from(path),
+        None => PathBuf::from(
+            std::env::var_os("HOME")
+                .ok_or_else(|| anyhow!("HOME is not set, so no service file"))?,
+        )
+        .join(".pg_service.conf"),
+    };
+    let contents = fs::read_to_string(&path).with_context(|| anyhow!("reading {:?}", path))?;
+    let settings = settings(&contents, service)
+        .with_context(|| anyhow!("parsing {:?}", path))?
+        .ok_or_else(|| anyhow!("{:?} has no [{}] service", path, service))?;
+    Ok(settings
+        .iter()
+        .map(|(key, value)| format!("{}={}", key, quote(value)))
+        .collect::<Vec<_>>()
+        .join(" "))
+}
+
+/// Put the service's settings after `fallback`'s, so they take precedence, and `fallback` only
+/// fills in what the service doesn't say.
+pub fn with_fallback(service: &str, fallback: &str) -> Result<String> {
+    if fallback.starts_with("postgresql://") || fallback.starts_with("postgres://") {
+        bail!("a service can only be combined with a key=value connection string, not a url");
+    }
+    Ok(format!("{} {}", fallback, service).trim().to_string())
+}
+
+/// The `key=value` lines in the `[service]` section, if there is one.
+fn settings(contents: &str, service: &str) -> Result<Option<Vec<(String, String)>>> {
+    let mut settings = None;
+    let mut in_service = false;
+    for (number, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Some(section) = line.strip_prefix('[') {
+            let section = section
+                .strip_suffix(']')
+                .ok_or_else(|| anyhow!("line {}: unterminated section name", number + 1))?;
+            in_service = section == service;
+            if in_service {
+                settings.get_or_insert_with(Vec::new);
+            }
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=') else {
+            bail!("line {}: expected key=value", number + 1);
+        };
+        if in_service {
+            if let Some(settings) = settings.as_mut() {
+                settings.push((key.trim().to_string(), value.trim().to_string()));
+            }
+        }
+    }
+    Ok(settings)
+}
+
+/// A connection string value, quoted if it has to be.
+fn quote(value: &str) -> String {
+    if !value.is_empty() && !value.contains(|c: char| c.is_whitespace() || c == '\'' || c == '\\') {
+        return value.to_string();
+    }
+    format!("'{}'", value.replace('\\', "\\\\").replace('\'', "\\'"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{quote, settings, with_fallback};
+
+    #[test]
+    fn service_settings() {
+        let contents = concat!(
+            "# comment\n",
+            "[other]\n",
+            "host=elsewhere\n",
+            "\n",
+            "[mydb]\n",
+            "host = db.example.com\n",
+            "dbname=app\n",
+            "[later]\n",
+            "port=5433\n",
+        );
+        assert_eq!(
+            Some(vec![
+                ("host".to_string(), "db.example.com".to_string()),
+                ("dbname".to_string(), "app".to_string()),
+            ]),
+            settings(contents, "mydb").unwrap()
+        );
+        assert_eq!(None, settings(contents, "missing").unwrap());
+        assert!(settings("[mydb]\nhost\n", "mydb").is_err());
+    }
+
+    #[test]
+    fn combined() {
+        assert_eq!("plain", quote("plain"));
+        assert_eq!(r"'it\'s a \\ test'", quote(r"it's a \ test"));
+        assert_eq!(
+            "user=postgres host=db",
+            with_fallback("host=db", "user=postgres").unwrap()
+        );
+        assert_eq!("host=db", with_fallback("host=db", "").unwrap());
+        assert!(with_fallback("host=db", "postgres://localhost").is_err());
+    }
+}
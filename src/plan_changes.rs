@@ -0,0 +1,69 @@
+use std::collections::HashMap;
+
+/// The mean plan time of each statement in `pg_stat_statements`, as of the last poll.
+#[derive(Default)]
+pub struct PlanTimes {
+    means: HashMap<i64, f64>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct PlanChange {
+    pub query_id: i64,
+    pub before_ms: f64,
+    pub after_ms: f64,
+}
+
+impl PlanTimes {
+    /// Record this poll's `(queryid, total_plan_time / plans)`, returning the statements whose
+    /// mean moved by more than `threshold_pct` percent since the last poll.
+    pub fn update(
+        &mut self,
+        means: impl IntoIterator<Item = (i64, f64)>,
+        threshold_pct: f64,
+    ) -> Vec<PlanChange> {
+        let means: HashMap<i64, f64> = means.into_iter().collect();
+        let mut changes: Vec<PlanChange> = means
+            .iter()
+            .filter_map(|(&query_id, &after_ms)| {
+                let before_ms = *self.means.get(&query_id)?;
+                if before_ms <= 0.0 {
+                    return None;
+                }
+                let change_pct = (after_ms - before_ms).abs() / before_ms * 100.0;
+                (change_pct > threshold_pct).then_some(PlanChange {
+                    query_id,
+                    before_ms,
+                    after_ms,
+                })
+            })
+            .collect();
+        changes.sort_by_key(|change| change.query_id);
+        self.means = means;
+        changes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PlanChange, PlanTimes};
+
+    #[test]
+    fn over_threshold() {
+        let mut times = PlanTimes::default();
+        assert!(times
+            .update([(1, 1.0), (2, 1.0), (3, 0.0)], 50.0)
+            .is_empty());
+        assert_eq!(
+            vec![PlanChange {
+                query_id: 2,
+                before_ms: 1.0,
+                after_ms: 0.4,
+            }],
+            times.update([(1, 1.5), (2, 0.4), (3, 9.0), (4, 1.0)], 50.0)
+        );
+        // compared to the last poll, not the first
+        assert!(times.update([(1, 2.0), (2, 0.5)], 50.0).is_empty());
+        // and forgetting those which have gone
+        assert!(times.update([(3, 90.0)], 50.0).is_empty());
+    }
+}
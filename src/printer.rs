@@ -1,69 +1,671 @@
-use crate::clean_ws;
-use chrono::{DateTime, SecondsFormat, Utc};
-use postgres::types::Oid;
+use std::collections::{HashMap, HashSet};
+use std::net::IpAddr;
+use std::sync::Mutex;
+
+use crate::cell::Cell;
+use crate::{clean_ws, normalize_query};
+use anyhow::{anyhow, Result};
+use bunyarrs::{vars, Bunyarr};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, SecondsFormat, Utc};
+use lazy_static::lazy_static;
+use postgres::types::{FromSql, Oid, Type};
 use postgres::{Column, Row};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+
+lazy_static! {
+    /// The unsupported types already warned about, so each is only warned about once.
+    static ref WARNED_TYPES: Mutex<HashSet<String>> = Mutex::new(HashSet::new());
+}
+
+pub fn headers(columns: &[Column]) -> Vec<Cell> {
+    columns.iter().map(|c| Cell::from(c.name())).collect()
+}
+
+pub fn row_to_cells(columns: &[Column], row: &Row) -> Vec<Cell> {
+    let mut cells = Vec::with_capacity(columns.len());
+    for (i, column) in columns.iter().enumerate() {
+        cells.push(match column.type_().name() {
+            "timestamptz" => tso(row.get(i)),
+            "oid" => number(row.get::<_, Option<Oid>>(i)),
+            "name" | "text" | "varchar" | "bpchar" | "citext" => {
+                auto(&row.get::<_, Option<String>>(i))
+            }
+            "int2" => number(row.get::<_, Option<i16>>(i)),
+            "int4" => number(row.get::<_, Option<i32>>(i)),
+            "int8" => number(row.get::<_, Option<i64>>(i)),
+            "float4" => number(row.get::<_, Option<f32>>(i)),
+            "float8" => number(row.get::<_, Option<f64>>(i)),
+            "numeric" => number(row.get::<_, Option<Numeric>>(i).map(|n| n.0)),
+            "bool" => auto(&row.get::<_, Option<bool>>(i)),
+            "json" | "jsonb" => auto(&row.get::<_, Option<serde_json::Value>>(i)),
+            "timestamp" => row
+                .get::<_, Option<NaiveDateTime>>(i)
+                .map(|ts| ts.format("%Y-%m-%dT%H:%M:%S%.6f").to_string())
+                .into(),
+            "date" => auto(&row.get::<_, Option<NaiveDate>>(i)),
+            "xid" | "xid8" | "cid" | "inet" | "cidr" | "interval" | "uuid" => row
+                .get::<_, Option<Decoded>>(i)
+                .map_or(Cell::Null, |decoded| decoded.0),
+            // e.g. an extension's type; only the text-like ones, such as ltree, can be read as a
+            // String, and anything else is NULL until the query casts it to text
+            type_name => {
+                let first = WARNED_TYPES
+                    .lock()
+                    .expect("unpoisoned")
+                    .insert(type_name.to_string());
+                if first {
+                    let column = column.name();
+                    Bunyarr::with_name("printer").warn(
+                        vars! { column, type_name },
+                        "unsupported column type; cast it to text",
+                    );
+                }
+                row.try_get::<_, Option<String>>(i).ok().flatten().into()
+            }
+        });
+    }
+    cells
+}
+
+/// Drop the rows whose `state` isn't one of `states`.
+pub fn filter_by_state(lines: &mut Vec<Vec<Cell>>, state_col: usize, states: &[String]) {
+    let mut header = true;
+    lines.retain(|row| std::mem::take(&mut header) || states.iter().any(|s| row[state_col] == *s));
+}
 
-pub fn convert_to_strings(
-    columns: &[Column],
-    rows: impl IntoIterator<Item = Row>,
-) -> Vec<Vec<String>> {
-    let columns = columns;
-    let headers: Vec<_> = columns.iter().map(|c| c.name().to_string()).collect();
+/// Collapse rows running the same (normalized) query into the first such row, and append a
+/// `count` column saying how many rows each represents.
+pub fn dedup_by_query(lines: &mut Vec<Vec<Cell>>, query_col: usize) {
+    let rows: Vec<_> = lines.drain(1..).collect();
+    lines[0].push("count".into());
 
-    let mut lines = Vec::with_capacity(32);
-    lines.push(headers);
+    let mut seen = HashMap::with_capacity(rows.len());
+    let mut counts: Vec<usize> = Vec::with_capacity(rows.len());
 
     for row in rows {
-        let mut strings = Vec::with_capacity(columns.len());
-        for (i, column) in columns.iter().enumerate() {
-            strings.push(match column.type_().name() {
-                "timestamptz" => tso(row.get(i)),
-                "oid" => auto(&row.get::<_, Option<Oid>>(i)),
-                "name" | "text" | "varchar" => auto(&row.get::<_, Option<String>>(i)),
-                "int4" => auto(&row.get::<_, Option<i32>>(i)),
-                other => panic!("unknown type: {:?}", other),
-            });
+        let key = normalize_query(&row[query_col]);
+        match seen.get(&key) {
+            Some(&idx) => counts[idx] += 1,
+            None => {
+                seen.insert(key, counts.len());
+                counts.push(1);
+                lines.push(row);
+            }
         }
+    }
+
+    for (line, count) in lines.iter_mut().skip(1).zip(counts) {
+        line.push(Cell::number(count));
+    }
+}
+
+/// The `(pid, query)` of each row to leave out of every snapshot.
+pub type Baseline = HashSet<(String, String)>;
+
+/// The active rows in a snapshot, to use as a baseline.
+pub fn baseline(
+    lines: &[Vec<Cell>],
+    pid_col: usize,
+    query_col: usize,
+    state_col: usize,
+) -> Baseline {
+    lines[1..]
+        .iter()
+        .filter(|row| row[state_col] == "active")
+        .map(|row| (row[pid_col].to_string(), row[query_col].to_string()))
+        .collect()
+}
+
+/// Drop the rows which were already there in the baseline, e.g. a monitoring connection's.
+pub fn subtract_baseline(
+    lines: &mut Vec<Vec<Cell>>,
+    baseline: &Baseline,
+    pid_col: usize,
+    query_col: usize,
+) {
+    let mut rows = lines.drain(1..).collect::<Vec<_>>();
+    rows.retain(|row| !baseline.contains(&(row[pid_col].to_string(), row[query_col].to_string())));
+    lines.extend(rows);
+}
+
+/// Where each column is, by name, so a snapshot's header row is only searched once. Columns
+/// appended later (e.g. the age columns) aren't in it, but the others don't move.
+pub struct ColumnIndex(HashMap<String, usize>);
+
+impl ColumnIndex {
+    pub fn build(headers: &[Cell]) -> ColumnIndex {
+        ColumnIndex(
+            headers
+                .iter()
+                .enumerate()
+                // the first, like `position`, if a custom query repeats a name
+                .rev()
+                .map(|(i, header)| (header.to_string(), i))
+                .collect(),
+        )
+    }
 
-        lines.push(strings);
+    pub fn get(&self, name: &str) -> Result<usize> {
+        self.0
+            .get(name)
+            .copied()
+            .ok_or_else(|| anyhow!("query has no {:?} column", name))
     }
+}
+
+/// Where the columns needed for the synthetic age columns are, in the activity output.
+pub struct ColIndices {
+    pub snapshot_at: usize,
+    pub state: usize,
+    pub backend_start: usize,
+    pub xact_start: usize,
+    pub query_start: usize,
+}
 
-    lines
+impl ColIndices {
+    /// `None` if any are missing, e.g. for a custom query.
+    pub fn find(columns: &ColumnIndex) -> Option<ColIndices> {
+        let find = |name: &str| columns.get(name).ok();
+        Some(ColIndices {
+            snapshot_at: find("snapshot_at")?,
+            state: find("state")?,
+            backend_start: find("backend_start")?,
+            xact_start: find("xact_start")?,
+            query_start: find("query_start")?,
+        })
+    }
 }
 
-pub fn render(lines: &[Vec<String>], mins: &mut [usize]) -> String {
+/// Append, as of the snapshot:
+///  * `xact_age_secs`: how long each `idle in transaction` session's transaction has been
+///    open; empty for every other state.
+///  * `query_age_secs`: how long the current (or last) query has been running.
+///  * `session_age_secs`: how long the session has been connected.
+pub fn add_age_columns(lines: &mut [Vec<Cell>], col_indices: &ColIndices) {
+    let (headers, rows) = lines.split_first_mut().expect("header row");
+    headers.push("xact_age_secs".into());
+    headers.push("query_age_secs".into());
+    headers.push("session_age_secs".into());
+
+    for row in rows {
+        let now = &row[col_indices.snapshot_at];
+        let xact_age = if row[col_indices.state] == "idle in transaction" {
+            age_secs(now, &row[col_indices.xact_start])
+        } else {
+            Cell::Null
+        };
+        let query_age = age_secs(now, &row[col_indices.query_start]);
+        let session_age = age_secs(now, &row[col_indices.backend_start]);
+        row.push(xact_age);
+        row.push(query_age);
+        row.push(session_age);
+    }
+}
+
+/// Append a `lock_count` column, of how many `pg_locks` rows each session's pid has.
+pub fn enrich_with_lock_counts(
+    lines: &mut [Vec<Cell>],
+    counts: &HashMap<i32, i64>,
+    pid_col: usize,
+) {
+    let (headers, rows) = lines.split_first_mut().expect("header row");
+    headers.push("lock_count".into());
+
+    for row in rows {
+        let count = match row[pid_col].parse::<i32>() {
+            Ok(pid) => Cell::number(counts.get(&pid).copied().unwrap_or_default()),
+            Err(_) => Cell::Null,
+        };
+        row.push(count);
+    }
+}
+
+/// Append a `session_id` column, of `<backend_start epoch>-<pid>`, which, unlike the pid, isn't
+/// reused after the session ends or the server restarts. Empty if there's no `backend_start`.
+pub fn add_session_id(lines: &mut [Vec<Cell>], pid_col: usize, backend_start_col: usize) {
+    let (headers, rows) = lines.split_first_mut().expect("header row");
+    headers.push("session_id".into());
+
+    for row in rows {
+        let session_id = match parse_ts(&row[backend_start_col]) {
+            Some(start) => format!("{}-{}", start.timestamp(), row[pid_col]).into(),
+            None => Cell::Null,
+        };
+        row.push(session_id);
+    }
+}
+
+/// Append a `query_hash` column, identifying each row's query independently of its literals,
+/// its pid, or the server's `query_id`.
+pub fn add_query_hash(lines: &mut [Vec<Cell>], query_col: usize) {
+    let (headers, rows) = lines.split_first_mut().expect("header row");
+    headers.push("query_hash".into());
+
+    for row in rows {
+        let hash = hash_query(&row[query_col]);
+        row.push(hash.into());
+    }
+}
+
+/// The first 16 hex characters of the SHA-256 of the normalized query.
+pub fn hash_query(s: &str) -> String {
+    let digest = Sha256::digest(normalize_query(s).as_bytes());
+    digest[..8].iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Move the columns named in `order` to the front, in that order, followed by the rest as they
+/// were. Names the snapshot doesn't have are ignored.
+pub fn reorder_columns(lines: &mut [Vec<Cell>], order: &[String]) {
+    let headers = &lines[0];
+    let mut permutation: Vec<usize> = order
+        .iter()
+        .filter_map(|name| headers.iter().position(|header| header == name))
+        .collect();
+    let rest: Vec<usize> = (0..headers.len())
+        .filter(|col| !permutation.contains(col))
+        .collect();
+    permutation.extend(rest);
+
+    for row in lines {
+        *row = permutation
+            .iter()
+            .map(|&col| std::mem::take(&mut row[col]))
+            .collect();
+    }
+}
+
+/// Append a `name` column of `hit / (read + hit)`, to three places; empty if there were neither.
+pub fn add_hit_ratio(lines: &mut [Vec<Cell>], name: &str, hit_col: usize, read_col: usize) {
+    let (headers, rows) = lines.split_first_mut().expect("header row");
+    headers.push(name.into());
+
+    for row in rows {
+        let ratio = match (row[hit_col].parse::<f64>(), row[read_col].parse::<f64>()) {
+            (Ok(hit), Ok(read)) if hit + read > 0.0 => {
+                Cell::number(format!("{:.3}", hit / (read + hit)))
+            }
+            _ => Cell::Null,
+        };
+        row.push(ratio);
+    }
+}
+
+/// Columns which change every snapshot, so shouldn't count as a row changing.
+pub const VOLATILE: &[&str] = &[
+    "snapshot_at",
+    "xact_age_secs",
+    "query_age_secs",
+    "session_age_secs",
+];
+
+/// Compare a snapshot against the previous one, by `pid`, returning only the rows which are new
+/// (`+`), changed (`~`) or gone (`-`), marked in a leading `diff` column.
+pub fn diff_by_pid(
+    prev: Option<&[Vec<Cell>]>,
+    lines: &[Vec<Cell>],
+    pid_col: usize,
+) -> Vec<Vec<Cell>> {
+    let headers = &lines[0];
+    let prev = match prev {
+        Some(prev) if prev[0] == *headers => &prev[1..],
+        _ => &[],
+    };
+
+    let volatile: Vec<usize> = headers
+        .iter()
+        .enumerate()
+        .filter(|(_, header)| VOLATILE.contains(&&***header))
+        .map(|(i, _)| i)
+        .collect();
+
+    let same = |a: &[Cell], b: &[Cell]| {
+        a.iter()
+            .zip(b)
+            .enumerate()
+            .all(|(i, (a, b))| a == b || volatile.contains(&i))
+    };
+
+    let before: HashMap<&str, &Vec<Cell>> = prev.iter().map(|row| (&*row[pid_col], row)).collect();
+    let now: HashMap<&str, &Vec<Cell>> =
+        lines[1..].iter().map(|row| (&*row[pid_col], row)).collect();
+
+    let marked = |marker: &str, row: &[Cell]| {
+        let mut line = Vec::with_capacity(row.len() + 1);
+        line.push(marker.into());
+        line.extend(row.iter().cloned());
+        line
+    };
+
+    let mut output = vec![marked("diff", headers)];
+
+    for row in &lines[1..] {
+        match before.get(&*row[pid_col]) {
+            None => output.push(marked("+", row)),
+            Some(old) if !same(old, row) => output.push(marked("~", row)),
+            Some(_) => (),
+        }
+    }
+
+    for row in prev {
+        if !now.contains_key(&*row[pid_col]) {
+            output.push(marked("-", row));
+        }
+    }
+
+    output
+}
+
+fn age_secs(now: &str, since: &str) -> Cell {
+    match (parse_ts(now), parse_ts(since)) {
+        (Some(now), Some(since)) => {
+            // our own query can start after now(), the start of its transaction
+            let micros = (now - since).num_microseconds().unwrap_or_default().max(0);
+            Cell::number(format!("{:.3}", micros as f64 / 1e6))
+        }
+        _ => Cell::Null,
+    }
+}
+
+fn parse_ts(s: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(s)
+        .ok()
+        .map(|ts| ts.with_timezone(&Utc))
+}
+
+/// A table from literals, header row first, for tests; `""` is NULL.
+#[cfg(test)]
+pub fn table(rows: &[&[&str]]) -> Vec<Vec<Cell>> {
+    rows.iter()
+        .map(|row| {
+            row.iter()
+                .map(|s| match *s {
+                    "" => Cell::Null,
+                    s => Cell::from(s),
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// How wide `render_capped` pads a table's columns, from `PSD_MIN_COLUMN_WIDTHS` and
+/// `PSD_MAX_COLUMN_WIDTH`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ColumnWidths {
+    /// The narrowest each column can be, in order; a floor wins over `max`.
+    pub mins: Vec<usize>,
+    /// The widest any column is padded to, so one long value only pushes the rest of its own
+    /// row along, rather than widening its column for every row.
+    pub max: Option<usize>,
+}
+
+pub fn render(lines: &[Vec<Cell>], mins: &mut [usize]) -> String {
+    render_capped(lines, mins, &ColumnWidths::default())
+}
+
+/// As `render`, but padding the columns as `widths` says.
+pub fn render_capped(lines: &[Vec<Cell>], mins: &mut [usize], widths: &ColumnWidths) -> String {
+    render_styled(lines, mins, widths, |_, _, cell| cell.to_string())
+}
+
+/// As `render_capped`, but writing each cell as `style(row, col, cell)`, e.g. to colour it; only
+/// the cell itself counts towards the column's width.
+pub fn render_styled(
+    lines: &[Vec<Cell>],
+    mins: &mut [usize],
+    widths: &ColumnWidths,
+    style: impl Fn(usize, usize, &str) -> String,
+) -> String {
+    for (min, floor) in mins.iter_mut().zip(&widths.mins) {
+        *min = (*min).max(*floor);
+    }
+    let max_width = widths.max.unwrap_or(usize::MAX);
     for line in lines {
         for (col, min) in line.iter().zip(mins.iter_mut()) {
-            if col.len() > *min {
-                *min = col.len();
+            // characters, not bytes, as that's what the padding counts
+            let width = col.chars().count();
+            if width > *min {
+                *min = width.min(max_width).max(*min);
             }
         }
     }
 
     let mut buf = String::with_capacity(lines.len() * 300);
-    for line in lines {
+    for (row, line) in lines.iter().enumerate() {
         let last = mins.len() - 1;
-        for (col, min) in line.iter().zip(mins.iter()).take(last) {
-            buf.push_str(&format!("{:1$}", col, min + 3));
+        for (i, (col, min)) in line.iter().zip(mins.iter()).enumerate().take(last) {
+            buf.push_str(&style(row, i, col));
+            let width = col.chars().count();
+            buf.extend(std::iter::repeat_n(' ', (min + 3).saturating_sub(width)));
+            // only when capped, so it's still readable
+            if width >= min + 3 {
+                buf.push(' ');
+            }
         }
-        buf.push_str(&line[last]);
+        buf.push_str(&style(row, last, &line[last]));
         buf.push('\n');
     }
 
     buf
 }
 
+/// A `numeric`, as its text, which the driver has no type for.
+struct Numeric(String);
+
+impl<'a> FromSql<'a> for Numeric {
+    fn from_sql(
+        _: &Type,
+        raw: &'a [u8],
+    ) -> std::result::Result<Numeric, Box<dyn std::error::Error + Sync + Send>> {
+        // the binary format: a header of four words, then base 10000 digits
+        let word = |i: usize| {
+            raw.get(i * 2..i * 2 + 2)
+                .map(|b| u16::from_be_bytes([b[0], b[1]]))
+                .ok_or("truncated numeric")
+        };
+        let digits = usize::from(word(0)?);
+        let weight = i32::from(word(1)? as i16);
+        let sign = word(2)?;
+        let scale = usize::from(word(3)?);
+        let digits = (0..digits)
+            .map(|i| word(4 + i))
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        // the `weight`th digit is the last before the point
+        let digit = |i: i32| usize::try_from(i).ok().and_then(|i| digits.get(i).copied());
+
+        let mut text = match sign {
+            0x0000 => String::new(),
+            0x4000 => "-".to_string(),
+            0xC000 => return Ok(Numeric("NaN".to_string())),
+            0xD000 => return Ok(Numeric("Infinity".to_string())),
+            0xF000 => return Ok(Numeric("-Infinity".to_string())),
+            _ => return Err("unknown numeric sign".into()),
+        };
+        text.push_str(
+            &digit(0)
+                .filter(|_| weight >= 0)
+                .unwrap_or_default()
+                .to_string(),
+        );
+        for i in 1..=weight {
+            text.push_str(&format!("{:04}", digit(i).unwrap_or_default()));
+        }
+        if scale > 0 {
+            let mut fraction = String::with_capacity(scale + 4);
+            let mut i = weight + 1;
+            while fraction.len() < scale {
+                fraction.push_str(&format!("{:04}", digit(i).unwrap_or_default()));
+                i += 1;
+            }
+            fraction.truncate(scale);
+            text.push('.');
+            text.push_str(&fraction);
+        }
+        Ok(Numeric(text))
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        *ty == Type::NUMERIC
+    }
+}
+
+/// The types the driver has nothing, or nothing exact, to read into, from their binary formats:
+/// transaction ids, addresses as `inet` prints them, intervals as seconds, and uuids.
+struct Decoded(Cell);
+
+impl<'a> FromSql<'a> for Decoded {
+    fn from_sql(
+        ty: &Type,
+        raw: &'a [u8],
+    ) -> std::result::Result<Decoded, Box<dyn std::error::Error + Sync + Send>> {
+        Ok(Decoded(match ty.name() {
+            "xid" | "cid" => Cell::number(u32::from_be_bytes(raw.try_into()?)),
+            "xid8" => Cell::number(u64::from_be_bytes(raw.try_into()?)),
+            "inet" | "cidr" => Cell::from(inet(raw)?),
+            "interval" => {
+                let raw: &[u8; 16] = raw.try_into()?;
+                let micros = i64::from_be_bytes(raw[..8].try_into()?);
+                let days = i32::from_be_bytes(raw[8..12].try_into()?);
+                let months = i32::from_be_bytes(raw[12..16].try_into()?);
+                // as `extract(epoch from ...)` counts them
+                let days = f64::from(days) + f64::from(months) * 365.25 / 12.0;
+                Cell::number(micros as f64 / 1e6 + days * 86400.0)
+            }
+            "uuid" => {
+                let hex: String = raw.iter().map(|b| format!("{:02x}", b)).collect();
+                if hex.len() != 32 {
+                    return Err("truncated uuid".into());
+                }
+                Cell::from(format!(
+                    "{}-{}-{}-{}-{}",
+                    &hex[..8],
+                    &hex[8..12],
+                    &hex[12..16],
+                    &hex[16..20],
+                    &hex[20..]
+                ))
+            }
+            other => return Err(format!("can't decode {}", other).into()),
+        }))
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        [
+            Type::XID,
+            Type::XID8,
+            Type::CID,
+            Type::INET,
+            Type::CIDR,
+            Type::INTERVAL,
+            Type::UUID,
+        ]
+        .contains(ty)
+    }
+}
+
+/// e.g. `10.0.0.1`, or `10.0.0.0/8`: the netmask is left off a single address, unless it's a
+/// `cidr`.
+fn inet(raw: &[u8]) -> std::result::Result<String, Box<dyn std::error::Error + Sync + Send>> {
+    let [family, netmask, is_cidr, len, addr @ ..] = raw else {
+        return Err("truncated inet".into());
+    };
+    let (addr, bits) = match (family, len) {
+        (2, 4) => (IpAddr::from(<[u8; 4]>::try_from(addr)?), 32),
+        (3, 16) => (IpAddr::from(<[u8; 16]>::try_from(addr)?), 128),
+        _ => return Err("unknown inet family".into()),
+    };
+    Ok(if *is_cidr == 0 && *netmask == bits {
+        addr.to_string()
+    } else {
+        format!("{}/{}", addr, netmask)
+    })
+}
+
 fn ts(ts: DateTime<Utc>) -> String {
     ts.to_rfc3339_opts(SecondsFormat::Micros, true)
 }
 
-fn tso(v: Option<DateTime<Utc>>) -> String {
-    v.map(ts).unwrap_or_default()
+fn tso(v: Option<DateTime<Utc>>) -> Cell {
+    v.map(ts).into()
+}
+
+fn auto<T: ToString>(v: &Option<T>) -> Cell {
+    v.as_ref().map(|v| clean_ws(&v.to_string())).into()
+}
+
+fn number<T: ToString>(v: Option<T>) -> Cell {
+    v.map_or(Cell::Null, Cell::number)
 }
 
-fn auto<T: ToString>(v: &Option<T>) -> String {
-    match v {
-        Some(v) => clean_ws(&v.to_string()),
-        None => String::new(),
+#[cfg(test)]
+mod tests {
+    use postgres::types::{FromSql, Type};
+
+    use super::{render, table, Decoded, Numeric};
+    use crate::cell::Cell;
+
+    #[test]
+    fn widths_count_characters() {
+        let lines = table(&[&["usename", "state"], &["zoë", "active"], &["bob", "idle"]]);
+        assert_eq!(
+            "usename   state\nzoë       active\nbob       idle\n",
+            render(&lines, &mut [0; 2])
+        );
+        let lines = table(&[&["a", "b"], &["ééééé", "x"], &["yyyyy", "x"]]);
+        assert_eq!(
+            "a       b\nééééé   x\nyyyyy   x\n",
+            render(&lines, &mut [0; 2])
+        );
+    }
+
+    #[test]
+    fn numeric_text() {
+        let numeric = |words: &[u16]| {
+            let raw: Vec<u8> = words.iter().flat_map(|w| w.to_be_bytes()).collect();
+            Numeric::from_sql(&Type::NUMERIC, &raw).unwrap().0
+        };
+        assert_eq!("0", numeric(&[0, 0, 0, 0]));
+        assert_eq!("12345.678", numeric(&[3, 1, 0, 3, 1, 2345, 6780]));
+        assert_eq!("-0.0001", numeric(&[1, (-1i16) as u16, 0x4000, 4, 1]));
+        assert_eq!("20000", numeric(&[1, 1, 0, 0, 2]));
+        assert_eq!("1.50", numeric(&[2, 0, 0, 2, 1, 5000]));
+        assert_eq!("NaN", numeric(&[0, 0, 0xC000, 0]));
+    }
+
+    #[test]
+    fn decoded_text() {
+        let decoded = |ty: Type, raw: &[u8]| Decoded::from_sql(&ty, raw).unwrap().0;
+        assert_eq!(
+            Cell::number(1234),
+            decoded(Type::XID, &1234u32.to_be_bytes())
+        );
+        assert_eq!(
+            "10.0.0.1",
+            &*decoded(Type::INET, &[2, 32, 0, 4, 10, 0, 0, 1])
+        );
+        assert_eq!(
+            "10.0.0.0/8",
+            &*decoded(Type::INET, &[2, 8, 0, 4, 10, 0, 0, 0])
+        );
+        assert_eq!(
+            "10.0.0.1/32",
+            &*decoded(Type::CIDR, &[2, 32, 1, 4, 10, 0, 0, 1])
+        );
+        let mut v6 = vec![3, 128, 0, 16];
+        v6.extend_from_slice(&[0; 15]);
+        v6.push(1);
+        assert_eq!("::1", &*decoded(Type::INET, &v6));
+
+        // 1 day, 2.5 seconds
+        let mut interval = 2_500_000i64.to_be_bytes().to_vec();
+        interval.extend_from_slice(&1i32.to_be_bytes());
+        interval.extend_from_slice(&0i32.to_be_bytes());
+        assert_eq!(Cell::number(86402.5), decoded(Type::INTERVAL, &interval));
+
+        let uuid: Vec<u8> = (0..16).collect();
+        assert_eq!(
+            "00010203-0405-0607-0809-0a0b0c0d0e0f",
+            &*decoded(Type::UUID, &uuid)
+        );
     }
 }
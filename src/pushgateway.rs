@@ -0,0 +1,109 @@
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use anyhow::{anyhow, Context, Result};
+
+use crate::cell::Cell;
+
+/// Per-database gauges, as of one snapshot.
+#[derive(Default)]
+struct Database {
+    active_backends: u64,
+    max_query_age: f64,
+    max_xact_age: f64,
+}
+
+/// Summarise an activity snapshot, after the age columns have been added, in the Prometheus
+/// text format.
+pub fn metrics(lines: &[Vec<Cell>]) -> String {
+    let (headers, rows) = lines.split_first().expect("header row");
+    let find = |name: &str| headers.iter().position(|header| header == name);
+
+    let mut databases: BTreeMap<&str, Database> = BTreeMap::new();
+    if let (Some(datname), Some(state)) = (find("datname"), find("state")) {
+        let query_age = find("query_age_secs");
+        let xact_age = find("xact_age_secs");
+        let age = |row: &[Cell], col: Option<usize>| -> f64 {
+            col.and_then(|col| row[col].parse().ok())
+                .unwrap_or_default()
+        };
+
+        for row in rows {
+            let database = databases.entry(row[datname].as_str()).or_default();
+            if row[state] == "active" {
+                database.active_backends += 1;
+                database.max_query_age = database.max_query_age.max(age(row, query_age));
+            }
+            database.max_xact_age = database.max_xact_age.max(age(row, xact_age));
+        }
+    }
+
+    let mut buf = String::with_capacity(256 + databases.len() * 200);
+    let mut gauge = |name: &str, value: &dyn Fn(&Database) -> String| {
+        buf.push_str(&format!("# TYPE {} gauge\n", name));
+        for (dbname, database) in &databases {
+            buf.push_str(&format!(
+                "{}{{dbname=\"{}\"}} {}\n",
+                name,
+                escape(dbname),
+                value(database)
+            ));
+        }
+    };
+    gauge("pg_active_backends", &|db| db.active_backends.to_string());
+    gauge("pg_max_query_age_seconds", &|db| {
+        db.max_query_age.to_string()
+    });
+    gauge("pg_max_xact_age_seconds", &|db| db.max_xact_age.to_string());
+    buf
+}
+
+/// Replace this job's metrics on the gateway.
+pub fn push(url: &str, job: &str, metrics: &str) -> Result<()> {
+    let url = format!("{}/metrics/job/{}", url.trim_end_matches('/'), job);
+    ureq::put(&url)
+        .timeout(Duration::from_secs(5))
+        .set("Content-Type", "text/plain; version=0.0.4")
+        .send_string(metrics)
+        .with_context(|| anyhow!("pushing metrics to {:?}", url))?;
+    Ok(())
+}
+
+fn escape(label: &str) -> String {
+    label
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::metrics;
+    use crate::printer::table;
+
+    #[test]
+    fn per_database() {
+        let lines = table(&[
+            &["datname", "state", "xact_age_secs", "query_age_secs"][..],
+            &["app", "active", "", "1.500"],
+            &["app", "active", "", "3.000"],
+            &["app", "idle in transaction", "9.000", "8.000"],
+            &["other", "idle in transaction", "2.000", "2.000"],
+        ]);
+
+        assert_eq!(
+            concat!(
+                "# TYPE pg_active_backends gauge\n",
+                "pg_active_backends{dbname=\"app\"} 2\n",
+                "pg_active_backends{dbname=\"other\"} 0\n",
+                "# TYPE pg_max_query_age_seconds gauge\n",
+                "pg_max_query_age_seconds{dbname=\"app\"} 3\n",
+                "pg_max_query_age_seconds{dbname=\"other\"} 0\n",
+                "# TYPE pg_max_xact_age_seconds gauge\n",
+                "pg_max_xact_age_seconds{dbname=\"app\"} 9\n",
+                "pg_max_xact_age_seconds{dbname=\"other\"} 2\n",
+            ),
+            metrics(&lines)
+        );
+    }
+}
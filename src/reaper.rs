@@ -0,0 +1,111 @@
+use std::time::Duration;
+
+use anyhow::Result;
+
+use crate::cell::Cell;
+use crate::printer::ColumnIndex;
+
+/// What to do about sessions breaking a `Rule`.
+#[derive(Clone, Copy)]
+pub enum Action {
+    /// Cancel queries which have been running too long.
+    Cancel,
+    /// Terminate sessions which have sat in a transaction, doing nothing, for too long.
+    TerminateIdleInTransaction,
+}
+
+impl Action {
+    /// The `state` a session must be in, for this to be done to it.
+    pub fn state(self) -> &'static str {
+        match self {
+            Action::Cancel => "active",
+            Action::TerminateIdleInTransaction => "idle in transaction",
+        }
+    }
+
+    /// The synthetic activity column which says how long it's been.
+    pub fn age_column(self) -> &'static str {
+        match self {
+            Action::Cancel => "query_age_secs",
+            Action::TerminateIdleInTransaction => "xact_age_secs",
+        }
+    }
+
+    /// Does it, for pid `$1`, but only if it's still in database `$2` and the state, and has
+    /// been for more than `$3` seconds; the snapshot could be out of date.
+    pub fn sql(self) -> &'static str {
+        match self {
+            Action::Cancel => concat!(
+                "select pg_cancel_backend(pid) from pg_stat_activity",
+                " where pid = $1 and datname = $2 and state = 'active'",
+                " and now() - query_start > make_interval(secs => $3)",
+                " and pid <> pg_backend_pid()"
+            ),
+            Action::TerminateIdleInTransaction => concat!(
+                "select pg_terminate_backend(pid) from pg_stat_activity",
+                " where pid = $1 and datname = $2 and state = 'idle in transaction'",
+                " and now() - xact_start > make_interval(secs => $3)",
+                " and pid <> pg_backend_pid()"
+            ),
+        }
+    }
+}
+
+/// Sessions to get rid of: those in `dbname` which have been at it for longer than `older_than`.
+/// The database is required, so a typo can't point this at every database on the server.
+pub struct Rule {
+    pub older_than: Duration,
+    pub dbname: String,
+}
+
+impl Rule {
+    /// The rows, after the age columns have been added, which `action` should be done to.
+    pub fn overdue<'a>(&self, lines: &'a [Vec<Cell>], action: Action) -> Result<Vec<&'a [Cell]>> {
+        let columns = ColumnIndex::build(&lines[0]);
+        let datname = columns.get("datname")?;
+        let state_col = columns.get("state")?;
+        let age = columns.get(action.age_column())?;
+        let state = action.state();
+        let limit = self.older_than.as_secs_f64();
+        Ok(lines[1..]
+            .iter()
+            .filter(|row| row[datname] == self.dbname && row[state_col] == state)
+            .filter(|row| row[age].parse::<f64>().is_ok_and(|age| age > limit))
+            .map(Vec::as_slice)
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{Action, Rule};
+    use crate::printer::table;
+
+    #[test]
+    fn only_the_database() {
+        let rule = Rule {
+            older_than: Duration::from_secs(60),
+            dbname: "app".to_string(),
+        };
+        let lines = table(&[
+            &["pid", "datname", "state", "query_age_secs"],
+            &["1", "app", "active", "60.500"],
+            &["2", "app", "active", "59.000"],
+            &["3", "other", "active", "600.000"],
+            &["4", "app", "idle", "600.000"],
+            &["5", "app", "active", ""],
+        ]);
+        let pids: Vec<&str> = rule
+            .overdue(&lines, Action::Cancel)
+            .unwrap()
+            .iter()
+            .map(|row| row[0].as_str())
+            .collect();
+        assert_eq!(vec!["1"], pids);
+        assert!(rule
+            .overdue(&lines, Action::TerminateIdleInTransaction)
+            .is_err());
+    }
+}
@@ -0,0 +1,202 @@
+use std::fs;
+use std::io::{BufRead, BufReader};
+
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde_json::Value;
+
+use crate::cell::Cell;
+use crate::printer::{self, ColumnWidths};
+use crate::writer::{Line, OutputFormat};
+
+/// Something read back from an output file.
+pub enum Item {
+    Snapshot {
+        when: Option<DateTime<Utc>>,
+        /// Header row first; empty if the snapshot had no rows and the format doesn't record
+        /// the header separately.
+        lines: Vec<Vec<Cell>>,
+    },
+    /// Anything that isn't a snapshot, e.g. the header or footer.
+    Other(Value),
+    /// A `# ` line, e.g. from `PSD_SNAPSHOT_METADATA`.
+    Comment(String),
+}
+
+type Input = BufReader<zstd::Decoder<'static, BufReader<fs::File>>>;
+
+/// Reads the items written by a `SnapshotWriter` back out, from json or msgpack files.
+pub struct Reader {
+    format: OutputFormat,
+    input: Input,
+}
+
+impl Reader {
+    pub fn open(path: &str) -> Result<Reader> {
+        let format = OutputFormat::from_path(path)?;
+        if let OutputFormat::Influx = format {
+            bail!("reading influx files back isn't supported; they're for Telegraf");
+        }
+        let file = fs::File::open(path).with_context(|| anyhow!("opening {:?}", path))?;
+        let input = BufReader::new(zstd::Decoder::new(file)?);
+        Ok(Reader { format, input })
+    }
+
+    pub fn next_item(&mut self) -> Result<Option<Item>> {
+        match self.format {
+            OutputFormat::Json => {
+                let mut line = String::new();
+                if 0 == self.input.read_line(&mut line)? {
+                    return Ok(None);
+                }
+                Ok(Some(from_json_line(&line)?))
+            }
+            OutputFormat::Msgpack => {
+                if self.input.fill_buf()?.is_empty() {
+                    return Ok(None);
+                }
+                let value: Value = rmp_serde::from_read(&mut self.input)
+                    .with_context(|| anyhow!("decoding msgpack value"))?;
+                Ok(Some(from_msgpack_value(value)?))
+            }
+            OutputFormat::Influx => unreachable!("rejected by open"),
+        }
+    }
+}
+
+impl Iterator for Reader {
+    type Item = Result<Item>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_item().transpose()
+    }
+}
+
+pub fn from_json_line(line: &str) -> Result<Item> {
+    if let Some(comment) = line.strip_prefix('#') {
+        return Ok(Item::Comment(comment.trim().to_string()));
+    }
+    let value: Value =
+        serde_json::from_str(line).with_context(|| anyhow!("parsing json line {:?}", line))?;
+    if value.get("records").is_none() {
+        return Ok(Item::Other(value));
+    }
+    let line: Line = serde_json::from_value(value)?;
+    Ok(Item::Snapshot {
+        when: line.when,
+        lines: from_records(line.records),
+    })
+}
+
+/// Snapshots are arrays, and comments strings, so anything else is a header or footer.
+pub fn from_msgpack_value(value: Value) -> Result<Item> {
+    if let Value::String(comment) = value {
+        return Ok(Item::Comment(comment));
+    }
+    if !value.is_array() {
+        return Ok(Item::Other(value));
+    }
+    let lines: Vec<Vec<Cell>> = serde_json::from_value(value)?;
+    Ok(Item::Snapshot {
+        when: snapshot_at(&lines),
+        lines,
+    })
+}
+
+fn from_records(records: Vec<serde_json::Map<String, Value>>) -> Vec<Vec<Cell>> {
+    let Some(first) = records.first() else {
+        return Vec::new();
+    };
+    let headers: Vec<String> = first.keys().cloned().collect();
+    let mut lines = Vec::with_capacity(records.len() + 1);
+    lines.push(
+        headers
+            .iter()
+            .map(|header| Cell::from(header.as_str()))
+            .collect(),
+    );
+    for record in &records {
+        lines.push(
+            headers
+                .iter()
+                .map(|header| Cell::from_json(record.get(header).cloned().unwrap_or_default()))
+                .collect(),
+        );
+    }
+    lines
+}
+
+/// msgpack snapshots don't carry a timestamp of their own, so use the activity query's.
+fn snapshot_at(lines: &[Vec<Cell>]) -> Option<DateTime<Utc>> {
+    let col = lines.first()?.iter().position(|h| h == "snapshot_at")?;
+    let value = lines.get(1)?.get(col)?;
+    DateTime::parse_from_rfc3339(value)
+        .ok()
+        .map(|ts| ts.with_timezone(&Utc))
+}
+
+/// The last snapshot in a file which has any rows.
+pub fn last_snapshot(path: &str) -> Result<Vec<Vec<Cell>>> {
+    let mut last = None;
+    for item in Reader::open(path)? {
+        if let Item::Snapshot { lines, .. } = item? {
+            if !lines.is_empty() {
+                last = Some(lines);
+            }
+        }
+    }
+    last.ok_or_else(|| anyhow!("{:?} contains no rows", path))
+}
+
+/// Print every snapshot in a file as a table, and everything else as a `#` comment.
+pub fn replay(path: &str, widths: &ColumnWidths) -> Result<()> {
+    for item in Reader::open(path)? {
+        print!("{}", render_item(&item?, widths));
+    }
+    Ok(())
+}
+
+pub fn render_item(item: &Item, widths: &ColumnWidths) -> String {
+    match item {
+        Item::Snapshot { when, lines } => {
+            let when = when
+                .map(|when| when.to_rfc3339_opts(SecondsFormat::Micros, true))
+                .unwrap_or_default();
+            let mut out = format!("# snapshot {}\n", when);
+            if let Some(headers) = lines.first().filter(|headers| !headers.is_empty()) {
+                let mut mins = vec![0; headers.len()];
+                out.push_str(&printer::render_capped(lines, &mut mins, widths));
+            }
+            out
+        }
+        Item::Other(value) => format!("# {}\n", value),
+        Item::Comment(_) => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{render_item, Item};
+    use crate::printer::{table, ColumnWidths};
+
+    #[test]
+    fn replay_and_tail_honour_column_widths() {
+        let item = Item::Snapshot {
+            when: None,
+            lines: table(&[
+                &["pid", "state", "query"],
+                &["1", "idle in transaction", "select 1"],
+            ]),
+        };
+        let widths = ColumnWidths {
+            mins: vec![6],
+            max: Some(5),
+        };
+        assert_eq!(
+            "# snapshot \n\
+             pid      state   query\n\
+             1        idle in transaction select 1\n",
+            render_item(&item, &widths)
+        );
+    }
+}
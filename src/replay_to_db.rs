@@ -0,0 +1,526 @@
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Context, Result};
+use bunyarrs::{vars, Bunyarr};
+use chrono::{DateTime, Utc};
+use postgres::types::ToSql;
+use postgres::{GenericClient, Statement};
+use rusqlite::types::Value;
+use serde_json::json;
+
+use crate::cell::Cell;
+use crate::replay::{Item, Reader};
+use crate::{connect_client, validate_conn_string};
+
+const TABLE: &str = "pg_stat_activity_history";
+
+/// Insert every row in an activity output file into `pg_stat_activity_history` in the target
+/// database, creating it if necessary, with the snapshot's time in `captured_at`.
+pub fn replay_to_db(source: &str, target: &str) -> Result<()> {
+    validate_conn_string(target).with_context(|| anyhow!("interpreting --target"))?;
+    let mut client = connect_client(target)?;
+    let mut transaction = client.transaction()?;
+
+    // re-prepared whenever the columns change, e.g. between files from different versions
+    let mut insert: Option<(Vec<String>, Statement)> = None;
+    let mut rows_inserted: u64 = 0;
+
+    for item in Reader::open(source)? {
+        let Item::Snapshot { when, lines } = item? else {
+            continue;
+        };
+        let Some((headers, rows)) = lines.split_first() else {
+            continue;
+        };
+        let headers: Vec<String> = headers.iter().map(|header| header.to_string()).collect();
+
+        let statement = match &insert {
+            Some((columns, statement)) if *columns == headers => statement.clone(),
+            _ => {
+                let types = migrate_postgres(&mut transaction, &headers, rows)?;
+                let statement = prepare_insert(&mut transaction, &headers, &types)?;
+                insert = Some((headers.clone(), statement.clone()));
+                statement
+            }
+        };
+
+        let mut savepoint = transaction.transaction()?;
+        match insert_rows(&mut savepoint, &statement, when, rows) {
+            Ok(inserted) => {
+                savepoint.commit()?;
+                rows_inserted += inserted;
+            }
+            // the types came from the first snapshot with the columns, which a later one needn't fit
+            Err(err) => {
+                drop(savepoint);
+                let types = postgres_columns(&mut transaction)?.into_iter().collect();
+                if !widen_columns(&mut transaction, &headers, rows, &types)? {
+                    return Err(err);
+                }
+                let types = postgres_columns(&mut transaction)?.into_iter().collect();
+                let statement = prepare_insert(&mut transaction, &headers, &types)?;
+                rows_inserted += insert_rows(&mut transaction, &statement, when, rows)?;
+                insert = Some((headers.clone(), statement));
+            }
+        }
+    }
+
+    transaction.commit()?;
+    Bunyarr::with_name("replay-to-db").info(vars! { rows_inserted }, "replayed");
+    Ok(())
+}
+
+fn prepare_insert(
+    client: &mut impl GenericClient,
+    headers: &[String],
+    types: &HashMap<String, String>,
+) -> Result<Statement> {
+    client
+        .prepare(&insert_into(headers, types))
+        .with_context(|| anyhow!("preparing insert into {}", TABLE))
+}
+
+fn insert_rows(
+    client: &mut impl GenericClient,
+    statement: &Statement,
+    when: Option<DateTime<Utc>>,
+    rows: &[Vec<Cell>],
+) -> Result<u64> {
+    for row in rows {
+        // empty strings are how older files wrote nulls
+        let values: Vec<Option<&str>> = row
+            .iter()
+            .map(|value| Some(value.as_str()).filter(|value| !value.is_empty()))
+            .collect();
+        let mut params: Vec<&(dyn ToSql + Sync)> = vec![&when];
+        params.extend(values.iter().map(|v| v as &(dyn ToSql + Sync)));
+        client
+            .execute(statement, &params)
+            .with_context(|| anyhow!("inserting into {}", TABLE))?;
+    }
+    Ok(rows.len() as u64)
+}
+
+/// Change any column which can't hold one of the `rows`' values to `text`, which can hold
+/// anything, e.g. a `numeric` column from a file with only numbers in, which this one has text
+/// in. Returns whether there were any.
+fn widen_columns(
+    client: &mut impl GenericClient,
+    headers: &[String],
+    rows: &[Vec<Cell>],
+    types: &HashMap<String, String>,
+) -> Result<bool> {
+    let mut widened = false;
+    for (i, column) in headers.iter().enumerate() {
+        let Some(column_type) = types.get(column).filter(|t| *t != "text") else {
+            continue;
+        };
+        let values: Vec<&str> = rows
+            .iter()
+            .filter_map(|row| row.get(i))
+            .map(|value| value.as_str())
+            .filter(|value| !value.is_empty())
+            .collect();
+        // which rolls back when it's dropped, as the cast might fail
+        let fits = client
+            .transaction()?
+            .query(
+                &format!("select v::{} from unnest($1::text[]) v", column_type),
+                &[&values],
+            )
+            .is_ok();
+        if !fits {
+            client
+                .batch_execute(&widen_column(column))
+                .with_context(|| anyhow!("changing {:?} in {} to text", column, TABLE))?;
+            Bunyarr::with_name("replay-to-db").warn(
+                vars! { column, column_type },
+                "column can't hold this file's values, changed it to text",
+            );
+            widened = true;
+        }
+    }
+    Ok(widened)
+}
+
+/// The same, into a SQLite database file.
+pub fn replay_to_sqlite(source: &str, path: &str) -> Result<()> {
+    let mut conn =
+        rusqlite::Connection::open(path).with_context(|| anyhow!("opening {:?}", path))?;
+    let transaction = conn.transaction()?;
+
+    let mut columns: Option<Vec<String>> = None;
+    let mut rows_inserted: u64 = 0;
+
+    for item in Reader::open(source)? {
+        let Item::Snapshot { when, lines } = item? else {
+            continue;
+        };
+        let Some((headers, rows)) = lines.split_first() else {
+            continue;
+        };
+        let headers: Vec<String> = headers.iter().map(|header| header.to_string()).collect();
+        if columns.as_ref() != Some(&headers) {
+            migrate_schema(&transaction, &headers)?;
+            columns = Some(headers.clone());
+        }
+
+        let mut statement = transaction
+            .prepare_cached(&insert_into_sqlite(&headers))
+            .with_context(|| anyhow!("preparing insert into {}", TABLE))?;
+        for row in rows {
+            let mut params = vec![captured_at(when)];
+            params.extend(row.iter().map(sqlite_value));
+            statement
+                .execute(rusqlite::params_from_iter(params))
+                .with_context(|| anyhow!("inserting into {}", TABLE))?;
+            rows_inserted += 1;
+        }
+    }
+
+    transaction.commit()?;
+    Bunyarr::with_name("replay-to-db").info(vars! { rows_inserted }, "replayed");
+    Ok(())
+}
+
+/// What to store a new column as, from its values in the snapshot which brought it: every
+/// number a `numeric`, every time a `timestamptz`, and anything else, or nothing, `text`. The
+/// values are sent as text, and cast by the server; a later value which doesn't fit makes the
+/// column `text`, in [widen_columns].
+fn column_type(rows: &[Vec<Cell>], column: usize) -> &'static str {
+    let values: Vec<&Cell> = rows
+        .iter()
+        .filter_map(|row| row.get(column))
+        .filter(|value| !value.is_empty())
+        .collect();
+    if values.is_empty() {
+        "text"
+    } else if values.iter().all(|value| matches!(value, Cell::Number(_))) {
+        "numeric"
+    } else if values
+        .iter()
+        .all(|value| DateTime::parse_from_rfc3339(value).is_ok())
+    {
+        "timestamptz"
+    } else {
+        "text"
+    }
+}
+
+/// Create the table, or bring an existing one up to date with `headers`, e.g. because the
+/// file is from a newer server, which has more columns. Columns the table has which `headers`
+/// doesn't are left alone, to be null for these rows. Returns the type of each column, as the
+/// table has it.
+fn migrate_postgres(
+    client: &mut impl GenericClient,
+    headers: &[String],
+    rows: &[Vec<Cell>],
+) -> Result<HashMap<String, String>> {
+    let types: Vec<&str> = (0..headers.len())
+        .map(|column| column_type(rows, column))
+        .collect();
+    client
+        .batch_execute(&create_table(headers, &types))
+        .with_context(|| anyhow!("creating {}", TABLE))?;
+    let names: Vec<String> = postgres_columns(client)?
+        .into_iter()
+        .map(|(name, _)| name)
+        .collect();
+
+    let (added, missing) = migration(&names, headers);
+    for column in added {
+        let i = headers.iter().position(|header| *header == column);
+        let column_type = i.map_or("text", |i| types[i]);
+        client
+            .batch_execute(&add_column(&column, column_type))
+            .with_context(|| anyhow!("adding {:?} to {}", column, TABLE))?;
+        Bunyarr::with_name("replay-to-db").info(vars! { column }, "added column");
+    }
+    for column in missing {
+        Bunyarr::with_name("replay-to-db").warn(
+            vars! { column },
+            "existing column isn't in this file, leaving it null",
+        );
+    }
+    Ok(postgres_columns(client)?.into_iter().collect())
+}
+
+/// Each column's name and type, in order.
+fn postgres_columns(client: &mut impl GenericClient) -> Result<Vec<(String, String)>> {
+    Ok(client
+        .query(
+            concat!(
+                "select attname::text, format_type(atttypid, atttypmod) from pg_attribute",
+                " where attrelid = to_regclass($1::text) and attnum > 0 and not attisdropped",
+                " order by attnum"
+            ),
+            &[&TABLE],
+        )
+        .with_context(|| anyhow!("reading the columns of {}", TABLE))?
+        .iter()
+        .map(|row| (row.get(0), row.get(1)))
+        .collect())
+}
+
+/// As [migrate_postgres], but SQLite stores each value with its own type, so the columns don't
+/// have one.
+fn migrate_schema(conn: &rusqlite::Connection, headers: &[String]) -> Result<()> {
+    conn.execute_batch(&create_table_sqlite(headers))
+        .with_context(|| anyhow!("creating {}", TABLE))?;
+    let existing = conn
+        .prepare("select name from pragma_table_info(?1)")?
+        .query_map([TABLE], |row| row.get(0))?
+        .collect::<rusqlite::Result<Vec<String>>>()
+        .with_context(|| anyhow!("reading the columns of {}", TABLE))?;
+
+    let (added, missing) = migration(&existing, headers);
+    for column in added {
+        conn.execute_batch(&format!(
+            "alter table {} add column {}",
+            TABLE,
+            quote_ident(&column)
+        ))
+        .with_context(|| anyhow!("adding {:?} to {}", column, TABLE))?;
+        Bunyarr::with_name("replay-to-db").info(vars! { column }, "added column");
+    }
+    for column in missing {
+        Bunyarr::with_name("replay-to-db").warn(
+            vars! { column },
+            "existing column isn't in this file, leaving it null",
+        );
+    }
+    Ok(())
+}
+
+/// `(added, missing)`: the headers the table doesn't have, and the table's columns which
+/// aren't in the headers.
+fn migration(existing: &[String], headers: &[String]) -> (Vec<String>, Vec<String>) {
+    let added = headers
+        .iter()
+        .filter(|header| !existing.contains(header))
+        .cloned()
+        .collect();
+    let missing = existing
+        .iter()
+        .filter(|column| *column != "captured_at" && !headers.contains(column))
+        .cloned()
+        .collect();
+    (added, missing)
+}
+
+fn add_column(name: &str, column_type: &str) -> String {
+    format!(
+        "alter table {} add column if not exists {} {}",
+        TABLE,
+        quote_ident(name),
+        column_type
+    )
+}
+
+fn widen_column(name: &str) -> String {
+    format!(
+        "alter table {} alter column {} type text",
+        TABLE,
+        quote_ident(name)
+    )
+}
+
+fn quote_ident(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+fn create_table(headers: &[String], types: &[&str]) -> String {
+    let mut columns = vec!["captured_at timestamptz".to_string()];
+    columns.extend(
+        headers
+            .iter()
+            .zip(types)
+            .map(|(header, column_type)| format!("{} {}", quote_ident(header), column_type)),
+    );
+    format!(
+        "create table if not exists {} ({})",
+        TABLE,
+        columns.join(", ")
+    )
+}
+
+fn create_table_sqlite(headers: &[String]) -> String {
+    let mut columns = vec!["captured_at".to_string()];
+    columns.extend(headers.iter().map(|header| quote_ident(header)));
+    format!(
+        "create table if not exists {} ({})",
+        TABLE,
+        columns.join(", ")
+    )
+}
+
+/// Cast to the types the table has, which make the rest text.
+fn insert_into(headers: &[String], types: &HashMap<String, String>) -> String {
+    let mut columns = vec!["captured_at".to_string()];
+    let mut values = vec!["$1".to_string()];
+    for (i, header) in headers.iter().enumerate() {
+        columns.push(quote_ident(header));
+        let column_type = types.get(header).map_or("text", |t| t.as_str());
+        values.push(format!("${}::text::{}", i + 2, column_type));
+    }
+    format!(
+        "insert into {} ({}) values ({})",
+        TABLE,
+        columns.join(", "),
+        values.join(", ")
+    )
+}
+
+fn insert_into_sqlite(headers: &[String]) -> String {
+    let mut columns = vec!["captured_at".to_string()];
+    columns.extend(headers.iter().map(|header| quote_ident(header)));
+    let values: Vec<String> = (1..=columns.len()).map(|i| format!("?{}", i)).collect();
+    format!(
+        "insert into {} ({}) values ({})",
+        TABLE,
+        columns.join(", "),
+        values.join(", ")
+    )
+}
+
+fn captured_at(when: Option<DateTime<Utc>>) -> Value {
+    when.map_or(Value::Null, |when| Value::Text(when.to_rfc3339()))
+}
+
+/// Numbers as SQLite's integers or reals, where they fit, so they compare as numbers.
+fn sqlite_value(cell: &Cell) -> Value {
+    match cell {
+        Cell::Null => Value::Null,
+        Cell::Text(s) => Value::Text(s.to_string()),
+        Cell::Number(s) => {
+            if let Ok(n) = s.parse::<i64>() {
+                Value::Integer(n)
+            } else if let Ok(n) = s.parse::<f64>() {
+                Value::Real(n)
+            } else {
+                Value::Text(s.to_string())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use rusqlite::types::Value;
+
+    use super::{
+        add_column, column_type, create_table, insert_into, insert_into_sqlite, migrate_schema,
+        migration, sqlite_value, widen_column,
+    };
+    use crate::cell::Cell;
+    use crate::printer::table;
+
+    fn strings(items: &[&str]) -> Vec<String> {
+        items.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn sql() {
+        let headers = strings(&["snapshot_at", "pid", "a\"b"]);
+        assert_eq!(
+            concat!(
+                "create table if not exists pg_stat_activity_history (captured_at timestamptz,",
+                " \"snapshot_at\" timestamptz, \"pid\" numeric, \"a\"\"b\" text)"
+            ),
+            create_table(&headers, &["timestamptz", "numeric", "text"])
+        );
+        let types: HashMap<String, String> = [
+            ("snapshot_at", "timestamp with time zone"),
+            ("pid", "integer"),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+        assert_eq!(
+            concat!(
+                "insert into pg_stat_activity_history (captured_at, \"snapshot_at\", \"pid\",",
+                " \"a\"\"b\") values ($1, $2::text::timestamp with time zone,",
+                " $3::text::integer, $4::text::text)"
+            ),
+            insert_into(&headers, &types)
+        );
+        assert_eq!(
+            concat!(
+                "insert into pg_stat_activity_history (captured_at, \"snapshot_at\", \"pid\",",
+                " \"a\"\"b\") values (?1, ?2, ?3, ?4)"
+            ),
+            insert_into_sqlite(&headers)
+        );
+    }
+
+    #[test]
+    fn types_from_values() {
+        let mut lines = table(&[
+            &["snapshot_at", "pid", "count", "state"],
+            &["2024-01-02T03:04:05.5Z", "", "lots", ""],
+            &["2024-01-02T03:04:06Z", "", "", ""],
+        ]);
+        lines[1][1] = Cell::number(12);
+        lines[2][1] = Cell::number("1.5");
+        let rows = &lines[1..];
+        // a config query's `count` needn't be a number
+        assert_eq!(
+            vec!["timestamptz", "numeric", "text", "text"],
+            (0..4).map(|i| column_type(rows, i)).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn migrate() {
+        let existing = strings(&["captured_at", "snapshot_at", "pid", "backend_xid"]);
+        let headers = strings(&["snapshot_at", "pid", "query_id"]);
+        assert_eq!(
+            (strings(&["query_id"]), strings(&["backend_xid"])),
+            migration(&existing, &headers)
+        );
+        assert_eq!(
+            "alter table pg_stat_activity_history add column if not exists \"query_id\" numeric",
+            add_column("query_id", "numeric")
+        );
+        assert_eq!(
+            "alter table pg_stat_activity_history alter column \"query_id\" type text",
+            widen_column("query_id")
+        );
+    }
+
+    #[test]
+    fn sqlite() {
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        migrate_schema(&conn, &strings(&["pid", "backend_xid"])).unwrap();
+        migrate_schema(&conn, &strings(&["pid", "query_id"])).unwrap();
+        let columns = conn
+            .prepare("select name from pragma_table_info('pg_stat_activity_history')")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<rusqlite::Result<Vec<String>>>()
+            .unwrap();
+        assert_eq!(
+            strings(&["captured_at", "pid", "backend_xid", "query_id"]),
+            columns
+        );
+
+        let values = [
+            Cell::number(12),
+            Cell::number("1.5"),
+            Cell::from("12"),
+            Cell::Null,
+        ];
+        assert_eq!(
+            vec![
+                Value::Integer(12),
+                Value::Real(1.5),
+                Value::Text("12".to_string()),
+                Value::Null
+            ],
+            values.iter().map(sqlite_value).collect::<Vec<_>>()
+        );
+    }
+}
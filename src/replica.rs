@@ -0,0 +1,49 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+/// How far behind its primary a standby is, written before each activity snapshot, as a line of
+/// its own, by `PSD_IS_REPLICA`.
+#[derive(Serialize)]
+pub struct Lag {
+    pub when: Option<DateTime<Utc>>,
+    pub replication_lag: Summary,
+}
+
+/// Empty when it couldn't be fetched, e.g. through a pooler which doesn't allow the functions.
+#[derive(Default, Serialize)]
+pub struct Summary {
+    /// WAL received but not yet replayed. `None` when not streaming, e.g. on a primary.
+    pub replaying_bytes: Option<i64>,
+    /// Since the last replayed transaction committed on the primary; this grows while the
+    /// primary is idle, too.
+    pub replaying_age_secs: Option<f64>,
+}
+
+/// The query for a `Summary`; the functions were renamed from `xlog` to `wal` in PostgreSQL 10.
+pub fn lag_sql(server_version_num: i32) -> &'static str {
+    if server_version_num >= 100000 {
+        concat!(
+            "select pg_wal_lsn_diff(pg_last_wal_receive_lsn(), pg_last_wal_replay_lsn())::int8,",
+            " extract(epoch from now() - pg_last_xact_replay_timestamp())::float8"
+        )
+    } else {
+        concat!(
+            "select pg_xlog_location_diff(pg_last_xlog_receive_location(),",
+            " pg_last_xlog_replay_location())::int8,",
+            " extract(epoch from now() - pg_last_xact_replay_timestamp())::float8"
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::lag_sql;
+
+    #[test]
+    fn version_gated_functions() {
+        assert!(lag_sql(150000).contains("pg_last_wal_receive_lsn()"));
+        assert!(lag_sql(100000).contains("pg_wal_lsn_diff("));
+        assert!(lag_sql(90600).contains("pg_last_xlog_replay_location()"));
+        assert!(!lag_sql(90600).contains("wal"));
+    }
+}
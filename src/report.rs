@@ -0,0 +1,185 @@
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde_json::json;
+
+use crate::cell::Cell;
+use crate::normalize_query;
+use crate::replay::{Item, Reader};
+use crate::timeline::Timeline;
+
+/// How many of the slowest queries to list.
+const SLOWEST: usize = 10;
+
+/// Write a standalone HTML page summarising an activity file: active sessions over time, the
+/// wait events seen, the slowest queries, and the timeline of each pid. The charts are drawn
+/// by Chart.js, from its CDN; everything else is in the page.
+pub fn report(path: &str, output: &str) -> Result<()> {
+    let mut report = Report::default();
+    for item in Reader::open(path)? {
+        if let Item::Snapshot { when, lines } = item? {
+            report.add(when, &lines);
+        }
+    }
+    fs::write(output, report.render(path)).with_context(|| anyhow!("writing {:?}", output))
+}
+
+#[derive(Default)]
+struct Report {
+    /// The time of each snapshot, and how many of its sessions were active.
+    active: Vec<(String, usize)>,
+    /// How many rows were seen waiting on each `wait_event_type: wait_event`.
+    waits: BTreeMap<String, u64>,
+    /// The longest each (normalised) query was seen running for, and by which pid.
+    slowest: HashMap<String, (f64, String)>,
+    timeline: Timeline,
+}
+
+impl Report {
+    fn add(&mut self, when: Option<DateTime<Utc>>, lines: &[Vec<Cell>]) {
+        self.timeline.add(when, lines);
+        let label = match when {
+            Some(when) => when.to_rfc3339_opts(SecondsFormat::Secs, true),
+            None => format!("#{}", self.active.len() + 1),
+        };
+
+        let Some((headers, rows)) = lines.split_first() else {
+            self.active.push((label, 0));
+            return;
+        };
+        let find = |name: &str| headers.iter().position(|header| header == name);
+        let state = find("state");
+        let is_active = |row: &[Cell]| state.is_none_or(|state| row[state] == "active");
+        self.active
+            .push((label, rows.iter().filter(|row| is_active(row)).count()));
+
+        if let (Some(wait_type), Some(wait)) = (find("wait_event_type"), find("wait_event")) {
+            for row in rows.iter().filter(|row| !row[wait_type].is_empty()) {
+                let event = format!("{}: {}", row[wait_type], row[wait]);
+                *self.waits.entry(event).or_default() += 1;
+            }
+        }
+
+        if let (Some(query), Some(age), Some(pid)) =
+            (find("query"), find("query_age_secs"), find("pid"))
+        {
+            for row in rows.iter().filter(|row| is_active(row)) {
+                let Ok(secs) = row[age].parse::<f64>() else {
+                    continue;
+                };
+                let slowest = self
+                    .slowest
+                    .entry(normalize_query(&row[query]))
+                    .or_insert((secs, row[pid].to_string()));
+                if secs > slowest.0 {
+                    *slowest = (secs, row[pid].to_string());
+                }
+            }
+        }
+    }
+
+    fn slowest(&self) -> Vec<(&str, f64, &str)> {
+        let mut slowest: Vec<_> = self
+            .slowest
+            .iter()
+            .map(|(query, (secs, pid))| (query.as_str(), *secs, pid.as_str()))
+            .collect();
+        slowest.sort_by(|a, b| {
+            b.1.partial_cmp(&a.1)
+                .unwrap_or(Ordering::Equal)
+                .then_with(|| a.0.cmp(b.0))
+        });
+        slowest.truncate(SLOWEST);
+        slowest
+    }
+
+    fn render(&self, path: &str) -> String {
+        let (labels, counts): (Vec<_>, Vec<_>) = self.active.iter().cloned().unzip();
+        let (events, waits): (Vec<&String>, Vec<u64>) = self.waits.iter().unzip();
+        let data = json!({
+            "labels": labels,
+            "active": counts,
+            "events": events,
+            "waits": waits,
+        });
+
+        let mut slowest = String::new();
+        for (query, secs, pid) in self.slowest() {
+            slowest.push_str(&format!(
+                "<tr><td>{:.3}</td><td>{}</td><td><code>{}</code></td></tr>\n",
+                secs,
+                escape(pid),
+                escape(query)
+            ));
+        }
+
+        format!(
+            include_str!("report.html"),
+            title = escape(path),
+            slowest = slowest,
+            timeline = escape(&self.timeline.render()),
+            // so nothing in there can end the script early
+            data = data.to_string().replace('<', "\\u003c"),
+        )
+    }
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Report;
+    use crate::printer::table;
+
+    #[test]
+    fn summarises() {
+        let headers: &[&str] = &[
+            "pid",
+            "state",
+            "wait_event_type",
+            "wait_event",
+            "query_age_secs",
+            "query",
+        ];
+        let mut report = Report::default();
+        report.add(
+            None,
+            &table(&[
+                headers,
+                &["1", "active", "Lock", "relation", "5.000", "select 1"],
+                &["2", "idle", "Client", "ClientRead", "9.000", "select 2"],
+            ]),
+        );
+        report.add(
+            None,
+            &table(&[
+                headers,
+                &["1", "active", "", "", "6.500", "select 3"],
+                &["3", "active", "Lock", "relation", "1.000", "</script>"],
+            ]),
+        );
+
+        assert_eq!(
+            vec![("#1".to_string(), 1), ("#2".to_string(), 2)],
+            report.active
+        );
+        assert_eq!(2, report.waits["Lock: relation"]);
+        assert_eq!(
+            vec![("select ?", 6.5, "1"), ("</script>", 1.0, "3")],
+            report.slowest()
+        );
+
+        let html = report.render("a&b.jsonl.zst");
+        assert!(html.contains("<title>pg-stat-dump report: a&amp;b.jsonl.zst</title>"));
+        assert!(html.contains("<td><code>&lt;/script&gt;</code></td>"));
+        assert!(!html.contains("\"</script>\""));
+    }
+}
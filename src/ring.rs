@@ -0,0 +1,64 @@
+use std::collections::VecDeque;
+
+use crate::cell::Cell;
+
+/// The last few activity snapshots, oldest first, so we can compare against history without
+/// re-reading the output file.
+pub struct SnapshotRing {
+    buf: VecDeque<Vec<Vec<Cell>>>,
+    max: usize,
+}
+
+impl SnapshotRing {
+    pub fn new(max: usize) -> SnapshotRing {
+        assert!(max > 0, "a ring must hold at least one snapshot");
+        SnapshotRing {
+            buf: VecDeque::with_capacity(max),
+            max,
+        }
+    }
+
+    /// Add a snapshot, forgetting the oldest if we're full.
+    pub fn push(&mut self, lines: Vec<Vec<Cell>>) {
+        if self.buf.len() == self.max {
+            self.buf.pop_front();
+        }
+        self.buf.push_back(lines);
+    }
+
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &Vec<Vec<Cell>>> {
+        self.buf.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.buf.len()
+    }
+
+    pub fn clear(&mut self) {
+        self.buf.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SnapshotRing;
+    use crate::cell::Cell;
+
+    fn snapshot(n: usize) -> Vec<Vec<Cell>> {
+        vec![vec!["n".into()], vec![Cell::number(n)]]
+    }
+
+    #[test]
+    fn keeps_the_most_recent() {
+        let mut ring = SnapshotRing::new(2);
+        assert_eq!(0, ring.len());
+        for n in 1..=3 {
+            ring.push(snapshot(n));
+        }
+        assert_eq!(2, ring.len());
+        assert_eq!(
+            vec![&snapshot(2), &snapshot(3)],
+            ring.iter().collect::<Vec<_>>()
+        );
+    }
+}
@@ -0,0 +1,70 @@
+use std::collections::BTreeMap;
+
+use anyhow::{anyhow, bail, Context, Result};
+
+use crate::cell::Cell;
+
+/// The most sessions each role should have, from e.g. `app:20;reporting:5`.
+pub type RoleLimits = BTreeMap<String, usize>;
+
+pub fn parse(list: &str) -> Result<RoleLimits> {
+    let mut limits = RoleLimits::new();
+    for pair in list.split(';') {
+        let pair = pair.trim();
+        if pair.is_empty() {
+            continue;
+        }
+        // role names can contain colons, limits can't
+        let Some((role, limit)) = pair.rsplit_once(':') else {
+            bail!("{:?} must be of the form 'rolename:max_connections'", pair);
+        };
+        let limit = limit
+            .trim()
+            .parse()
+            .with_context(|| anyhow!("parsing the limit in {:?}", pair))?;
+        if limits.insert(role.trim().to_string(), limit).is_some() {
+            bail!("{:?} is limited more than once", role);
+        }
+    }
+    Ok(limits)
+}
+
+/// `(role, sessions, limit)` for each role with more sessions in the snapshot than it's allowed.
+pub fn over_limit(
+    lines: &[Vec<Cell>],
+    usename_col: usize,
+    limits: &RoleLimits,
+) -> Vec<(String, usize, usize)> {
+    let mut sessions: BTreeMap<&str, usize> = BTreeMap::new();
+    for row in &lines[1..] {
+        *sessions.entry(row[usename_col].as_str()).or_default() += 1;
+    }
+    limits
+        .iter()
+        .filter_map(|(role, &limit)| {
+            let sessions = sessions.get(role.as_str()).copied().unwrap_or_default();
+            (sessions > limit).then(|| (role.to_string(), sessions, limit))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{over_limit, parse};
+    use crate::printer::table;
+
+    #[test]
+    fn parse_and_count() {
+        let limits = parse(" app:2; odd:name:0 ;;reporting: 5").unwrap();
+        assert_eq!(3, limits.len());
+        assert!(parse("app").is_err());
+        assert!(parse("app:lots").is_err());
+        assert!(parse("app:1;app:2").is_err());
+
+        let lines = table(&[&["usename"], &["app"], &["app"], &["app"], &["odd:name"]]);
+        assert_eq!(
+            vec![("app".to_string(), 3, 2), ("odd:name".to_string(), 1, 0)],
+            over_limit(&lines, 0, &limits)
+        );
+    }
+}
@@ -0,0 +1,190 @@
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+
+use anyhow::{anyhow, Context, Result};
+
+use crate::cell::Cell;
+use crate::printer;
+use crate::replay::last_snapshot;
+
+/// One `queryid`'s counters, summed over every user and database it was seen for.
+#[derive(Default)]
+struct Totals {
+    query: String,
+    calls: f64,
+    total_exec_time: f64,
+    rows: f64,
+}
+
+/// Compare the last `pg_stat_statements` snapshot in each of two files, and print what changed
+/// between them, most expensive first.
+pub fn stmt_delta(before: &str, after: &str) -> Result<()> {
+    let report = report(&last_snapshot(before)?, &last_snapshot(after)?)?;
+    print!("{}", printer::render(&report, &mut [0; 6]));
+    Ok(())
+}
+
+fn report(before: &[Vec<Cell>], after: &[Vec<Cell>]) -> Result<Vec<Vec<Cell>>> {
+    let before = totals(before)?;
+    let after = totals(after)?;
+
+    let mut changes = Vec::with_capacity(after.len());
+    for (queryid, now) in &after {
+        let (status, prev) = match before.get(queryid) {
+            Some(prev) => ("changed", prev),
+            None => ("new", &Totals::default()),
+        };
+        changes.push((
+            status,
+            queryid,
+            Some((
+                now.calls - prev.calls,
+                now.total_exec_time - prev.total_exec_time,
+                now.rows - prev.rows,
+            )),
+            &now.query,
+        ));
+    }
+    for (queryid, prev) in &before {
+        if !after.contains_key(queryid) {
+            changes.push(("dropped", queryid, None, &prev.query));
+        }
+    }
+
+    // dropped queries have no delta, so go last
+    changes.sort_by(|(_, _, a, _), (_, _, b, _)| match (a, b) {
+        (Some(a), Some(b)) => b.1.partial_cmp(&a.1).unwrap_or(Ordering::Equal),
+        (a, b) => b.is_some().cmp(&a.is_some()),
+    });
+
+    let mut lines = vec![[
+        "status",
+        "queryid",
+        "delta_calls",
+        "delta_total_exec_time",
+        "delta_rows",
+        "query",
+    ]
+    .iter()
+    .map(|&header| Cell::from(header))
+    .collect::<Vec<_>>()];
+
+    for (status, queryid, delta, query) in changes {
+        let (calls, time, rows) = match delta {
+            Some((calls, time, rows)) => (
+                Cell::number(calls),
+                Cell::number(format!("{:.3}", time)),
+                Cell::number(rows),
+            ),
+            None => Default::default(),
+        };
+        lines.push(vec![
+            status.into(),
+            Cell::number(queryid),
+            calls,
+            time,
+            rows,
+            query.as_str().into(),
+        ]);
+    }
+
+    Ok(lines)
+}
+
+fn totals(lines: &[Vec<Cell>]) -> Result<BTreeMap<String, Totals>> {
+    let (headers, rows) = lines.split_first().expect("header row");
+    let find = |names: &[&str]| {
+        headers
+            .iter()
+            .position(|header| names.contains(&header.as_str()))
+            .ok_or_else(|| anyhow!("no column named {:?}", names[0]))
+    };
+    let queryid = find(&["queryid"])?;
+    let calls = find(&["calls"])?;
+    // renamed in PostgreSQL 13
+    let total_exec_time = find(&["total_exec_time", "total_time"])?;
+    let rows_col = find(&["rows"])?;
+    let query = headers.iter().position(|header| header == "query");
+
+    // NULL counts as nothing, but anything else has to be a number
+    let number = |row: &[Cell], col: usize| -> Result<f64> {
+        if row[col].is_empty() {
+            return Ok(0.);
+        }
+        row[col].parse::<f64>().with_context(|| {
+            anyhow!(
+                "{} for queryid {} is {:?}, not a number",
+                headers[col],
+                row[queryid],
+                row[col].as_str()
+            )
+        })
+    };
+
+    let mut totals: BTreeMap<String, Totals> = BTreeMap::new();
+    for row in rows {
+        let calls = number(row, calls)?;
+        let total_exec_time = number(row, total_exec_time)?;
+        let rows = number(row, rows_col)?;
+        let entry = totals.entry(row[queryid].to_string()).or_default();
+        entry.calls += calls;
+        entry.total_exec_time += total_exec_time;
+        entry.rows += rows;
+        if let Some(query) = query {
+            entry.query = row[query].to_string();
+        }
+    }
+    Ok(totals)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::report;
+    use crate::printer::table;
+
+    #[test]
+    fn matches_by_queryid() {
+        let headers: &[&str] = &["queryid", "calls", "total_exec_time", "rows", "query"];
+        let before = table(&[
+            headers,
+            &["1", "10", "100.0", "10", "select 1"],
+            &["2", "5", "50.0", "5", "select 2"],
+            &["3", "1", "1.0", "1", "select 3"],
+        ]);
+        let after = table(&[
+            headers,
+            &["1", "11", "101.0", "11", "select 1"],
+            // the same query for another user
+            &["2", "10", "500.0", "10", "select 2"],
+            &["2", "1", "10.0", "1", "select 2"],
+            &["4", "2", "20.0", "2", "select 4"],
+        ]);
+
+        assert_eq!(
+            table(&[
+                &[
+                    "status",
+                    "queryid",
+                    "delta_calls",
+                    "delta_total_exec_time",
+                    "delta_rows",
+                    "query"
+                ],
+                &["changed", "2", "6", "460.000", "6", "select 2"],
+                &["new", "4", "2", "20.000", "2", "select 4"],
+                &["changed", "1", "1", "1.000", "1", "select 1"],
+                &["dropped", "3", "", "", "", "select 3"],
+            ]),
+            report(&before, &after).unwrap()
+        );
+    }
+
+    #[test]
+    fn rejects_non_numbers() {
+        let headers: &[&str] = &["queryid", "calls", "total_exec_time", "rows"];
+        let lines = table(&[headers, &["1", "10", "", "10"]]);
+        assert!(report(&lines, &lines).is_ok());
+        let bad = table(&[headers, &["1", "10", "12 ms", "10"]]);
+        assert!(report(&lines, &bad).is_err());
+    }
+}
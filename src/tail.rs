@@ -0,0 +1,104 @@
+use std::fs;
+use std::io::{Cursor, ErrorKind, Read};
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Context, Result};
+use zstd::stream::raw::{Decoder, InBuffer, Operation, OutBuffer};
+
+use crate::printer::ColumnWidths;
+use crate::replay::{self, Item};
+use crate::writer::OutputFormat;
+
+/// Print the last snapshot already in a file, then each new one as it is written, forever.
+///
+/// The file is still being written, so may end part way through a zstd frame, or part way
+/// through an item; we keep what we've got, and try again when the file grows.
+pub fn tail(path: &str, widths: &ColumnWidths) -> Result<()> {
+    let format = OutputFormat::from_path(path)?;
+    let mut file = fs::File::open(path).with_context(|| anyhow!("opening {:?}", path))?;
+    let mut decoder = Decoder::new()?;
+    let mut pending = Vec::with_capacity(64 * 1024);
+
+    let mut items = Vec::new();
+    decompress(&mut file, &mut decoder, &mut pending)?;
+    take_items(format, &mut pending, &mut items)?;
+    if let Some(last) = items
+        .iter()
+        .rposition(|item| matches!(item, Item::Snapshot { .. }))
+    {
+        print!("{}", replay::render_item(&items[last], widths));
+    }
+
+    loop {
+        if !decompress(&mut file, &mut decoder, &mut pending)? {
+            std::thread::sleep(Duration::from_secs(1));
+            continue;
+        }
+        items.clear();
+        take_items(format, &mut pending, &mut items)?;
+        for item in &items {
+            print!("{}", replay::render_item(item, widths));
+        }
+    }
+}
+
+/// Decompress whatever has been added to the file since we last looked, returning whether
+/// there was anything.
+fn decompress(file: &mut fs::File, decoder: &mut Decoder, pending: &mut Vec<u8>) -> Result<bool> {
+    let mut compressed = Vec::new();
+    file.read_to_end(&mut compressed)?;
+    if compressed.is_empty() {
+        return Ok(false);
+    }
+
+    let mut input = InBuffer::around(&compressed);
+    let mut buf = vec![0u8; 128 * 1024];
+    loop {
+        let mut output = OutBuffer::around(buf.as_mut_slice());
+        decoder.run(&mut input, &mut output)?;
+        let written = output.pos();
+        pending.extend_from_slice(&buf[..written]);
+        // a full buffer means the decoder may be holding more back
+        if input.pos() == compressed.len() && written < buf.len() {
+            break;
+        }
+    }
+    Ok(true)
+}
+
+/// Move every complete item from the front of `pending` to `items`.
+fn take_items(format: OutputFormat, pending: &mut Vec<u8>, items: &mut Vec<Item>) -> Result<()> {
+    match format {
+        OutputFormat::Json => {
+            let Some(end) = pending.iter().rposition(|&b| b == b'\n') else {
+                return Ok(());
+            };
+            let complete: Vec<u8> = pending.drain(..=end).collect();
+            for line in std::str::from_utf8(&complete)?.lines() {
+                items.push(replay::from_json_line(line)?);
+            }
+        }
+        OutputFormat::Msgpack => {
+            let mut cursor = Cursor::new(pending.as_slice());
+            let mut consumed = 0;
+            loop {
+                match rmp_serde::from_read(&mut cursor) {
+                    Ok(value) => {
+                        items.push(replay::from_msgpack_value(value)?);
+                        consumed = cursor.position() as usize;
+                    }
+                    Err(rmp_serde::decode::Error::InvalidMarkerRead(e))
+                    | Err(rmp_serde::decode::Error::InvalidDataRead(e))
+                        if e.kind() == ErrorKind::UnexpectedEof =>
+                    {
+                        break
+                    }
+                    Err(e) => return Err(e).with_context(|| anyhow!("decoding msgpack value")),
+                }
+            }
+            pending.drain(..consumed);
+        }
+        OutputFormat::Influx => bail!("tailing influx files isn't supported; they're for Telegraf"),
+    }
+    Ok(())
+}
@@ -0,0 +1,438 @@
+/// Every environment variable `config()` reads: name, default, and what it does.
+const ENV_VARS: &[(&str, &str, &str)] = &[
+    (
+        "PSD_ENV_PREFIX",
+        "",
+        "read every other variable with this prefix instead of PSD_, e.g. APP2_ for APP2_CONN_STRING",
+    ),
+    (
+        "PSD_CONN_STRING",
+        "",
+        "required, e.g. host=localhost user=postgres sslmode=require",
+    ),
+    (
+        "PSD_CONN_STRING_FILE",
+        "",
+        "read PSD_CONN_STRING from this file instead, e.g. a Docker secret",
+    ),
+    (
+        "PSD_CONN_STRING_STDIN",
+        "0",
+        "read PSD_CONN_STRING from the first line of stdin instead",
+    ),
+    (
+        "PSD_PGSERVICE",
+        "",
+        "connect with this service from ~/.pg_service.conf (or PGSERVICEFILE), filling in from PSD_CONN_STRING",
+    ),
+    (
+        "PSD_POLL_INTERVAL_SECS",
+        "53",
+        "how often to snapshot pg_stat_activity",
+    ),
+    (
+        "PSD_SLOW_FETCH_SECS",
+        "",
+        "warn if a query takes longer than this; defaults to half PSD_POLL_INTERVAL_SECS",
+    ),
+    (
+        "PSD_MAX_UPTIME_SECS",
+        "3600",
+        "exit after this long; 0 runs forever",
+    ),
+    (
+        "PSD_INITIAL_RETRY_SECS",
+        "",
+        "keep retrying the first connection for this long",
+    ),
+    (
+        "PSD_QUERY_FILE",
+        "",
+        "file containing a query to run instead of the built-in one",
+    ),
+    (
+        "PSD_EXTRA_COLUMNS",
+        "",
+        "';'-separated 'expression as alias' columns to add to the built-in query",
+    ),
+    (
+        "PSD_FILTER_BACKEND_TYPE",
+        "",
+        "comma-separated backend types to report, e.g. client backend",
+    ),
+    (
+        "PSD_FILTER_STATE",
+        "",
+        "comma-separated states to write and send activity rows for, e.g. active,idle in transaction",
+    ),
+    (
+        "PSD_CONFIG_FILE",
+        "",
+        "TOML file of additional queries, like the one below",
+    ),
+    (
+        "PSD_DELTA_MODE",
+        "0",
+        "report counter_columns as the difference since the last poll",
+    ),
+    (
+        "PSD_TOP_N",
+        "",
+        "only report the N longest-running sessions",
+    ),
+    (
+        "PSD_DEDUPLICATE_QUERIES",
+        "0",
+        "collapse rows running the same query, adding a count column",
+    ),
+    (
+        "PSD_COLUMN_ORDER",
+        "",
+        "comma-separated activity columns to write first, in this order, e.g. pid,state,query",
+    ),
+    (
+        "PSD_MIN_COLUMN_WIDTHS",
+        "",
+        "comma-separated narrowest width of each column, in order, e.g. 8,32, in live, replay, tail and diff",
+    ),
+    (
+        "PSD_MAX_COLUMN_WIDTH",
+        "",
+        "the widest any column is padded to, however long its values, in live, replay, tail and diff",
+    ),
+    (
+        "PSD_DEDUPLICATE_ACROSS_SNAPSHOTS",
+        "0",
+        "only write the first row seen running each query, in each file",
+    ),
+    (
+        "PSD_EMIT_DIFFS_ONLY",
+        "0",
+        "only write rows which are new, changed or gone since the last snapshot",
+    ),
+    (
+        "PSD_ADD_LOCK_COUNTS",
+        "0",
+        "add a lock_count column, of each session's rows in pg_locks",
+    ),
+    (
+        "PSD_IS_REPLICA",
+        "0",
+        "write the replication lag of a standby before each activity snapshot",
+    ),
+    (
+        "PSD_SNAPSHOT_METADATA",
+        "0",
+        "write a '# snapshot=N rows=M fetch_ms=T' comment before each activity snapshot",
+    ),
+    (
+        "PSD_SLOW_QUERIES_TOP_K",
+        "",
+        "at exit, write this many of the longest-running queries seen to slow-queries-<time>.txt",
+    ),
+    (
+        "PSD_WRITE_QUEUE_SNAPSHOTS",
+        "",
+        "compress on another thread, dropping snapshots rather than waiting if this many are queued",
+    ),
+    (
+        "PSD_OUTPUT_COMPRESS_AFTER_BYTES",
+        "",
+        "flush the output once this many bytes are waiting, instead of after every snapshot",
+    ),
+    (
+        "PSD_MEMORY_SNAPSHOTS",
+        "1",
+        "how many recent activity snapshots to keep in memory",
+    ),
+    (
+        "PSD_ENABLE_INDEX_PROGRESS",
+        "0",
+        "also poll pg_stat_progress_create_index, into stat-index-progress files (PostgreSQL 12+)",
+    ),
+    (
+        "PSD_ENABLE_ANALYZE_PROGRESS",
+        "0",
+        "also poll pg_stat_progress_analyze, into stat-analyze-progress files (PostgreSQL 13+)",
+    ),
+    (
+        "PSD_ENABLE_SUBSCRIPTIONS",
+        "0",
+        "also poll pg_stat_subscription, into stat-subscriptions files (PostgreSQL 10+)",
+    ),
+    (
+        "PSD_ENABLE_WAL_STATS",
+        "0",
+        "also poll pg_stat_wal, as the difference since the last poll, into stat-wal files (PostgreSQL 14+)",
+    ),
+    (
+        "PSD_ENABLE_ARCHIVER",
+        "0",
+        "also poll pg_stat_archiver, into stat-archiver files, warning on new failures",
+    ),
+    (
+        "PSD_ENABLE_SSL_STATS",
+        "0",
+        "also poll pg_stat_ssl for each client connection, into stat-ssl files (PostgreSQL 12+)",
+    ),
+    (
+        "PSD_ENABLE_LOCK_WAITS",
+        "0",
+        "also poll pg_locks for waiting sessions and who they wait on, into stat-lock-waits files (PostgreSQL 9.6+)",
+    ),
+    (
+        "PSD_ENABLE_TABLE_STATS",
+        "0",
+        "also poll pg_stat_user_tables, into stat-tables files",
+    ),
+    (
+        "PSD_TABLE_STATS_TOP_N",
+        "",
+        "only report the N tables with the most live tuples",
+    ),
+    (
+        "PSD_ENABLE_INDEX_STATS",
+        "0",
+        "also poll pg_stat_user_indexes, into stat-indexes files",
+    ),
+    (
+        "PSD_ALERT_UNUSED_INDEX",
+        "0",
+        "with PSD_ENABLE_INDEX_STATS, warn at exit about indexes which were never scanned",
+    ),
+    (
+        "PSD_ENABLE_TABLE_IO",
+        "0",
+        "also poll pg_statio_user_tables, as the difference since the last poll, with heap_hit_ratio, into stat-table-io files",
+    ),
+    (
+        "PSD_ENABLE_RELATION_SIZES",
+        "0",
+        "also poll the size of each table and index, into stat-sizes files; growth in delta mode",
+    ),
+    (
+        "PSD_DETECT_PLAN_CHANGES",
+        "0",
+        "warn when a statement's mean plan time in pg_stat_statements moves a lot (PostgreSQL 14+)",
+    ),
+    (
+        "PSD_PLAN_CHANGE_THRESHOLD_PCT",
+        "50",
+        "how far, in percent, the mean plan time must move between polls to warn",
+    ),
+    (
+        "PSD_BASELINE_FILE",
+        "",
+        "leave out rows already active in the last snapshot of this dump",
+    ),
+    ("PSD_OUTPUT_FORMAT", "json", "json, msgpack or influx"),
+    (
+        "PSD_OUTPUT_EXTRA_FILE",
+        "",
+        "also write the activity output here, uncompressed, e.g. a FIFO",
+    ),
+    (
+        "PSD_USE_LOCAL_TIME",
+        "0",
+        "name output files by the local time, instead of UTC",
+    ),
+    (
+        "PSD_TIMESTAMP_FORMAT",
+        "",
+        "strftime format for the timestamp in output file names, instead of RFC 3339",
+    ),
+    (
+        "PSD_MAX_FILE_AGE_SECS",
+        "",
+        "start new output files once they are this old",
+    ),
+    (
+        "PSD_ROLE_CONNECTION_LIMITS",
+        "",
+        "warn when a role has more sessions than allowed, e.g. app:20;reporting:5",
+    ),
+    (
+        "PSD_ALERT_BLOCKED_CHAIN_DEPTH",
+        "",
+        "print to stderr when sessions are blocked more than this many deep",
+    ),
+    (
+        "PSD_PUSHGATEWAY_URL",
+        "",
+        "push per-database gauges to this Prometheus Pushgateway after each poll",
+    ),
+    (
+        "PSD_PUSHGATEWAY_JOB",
+        "pg-stat-dump",
+        "the Pushgateway job name",
+    ),
+    (
+        "PSD_INFLUX_URL",
+        "",
+        "write activity points to this InfluxDB v2 server, e.g. http://localhost:8086",
+    ),
+    ("PSD_INFLUX_TOKEN", "", "the API token for PSD_INFLUX_URL"),
+    ("PSD_INFLUX_ORG", "", "the organisation to write to"),
+    ("PSD_INFLUX_BUCKET", "", "the bucket to write to"),
+    (
+        "PSD_ELASTICSEARCH_URL",
+        "",
+        "bulk-index each activity row into elasticsearch, e.g. http://localhost:9200",
+    ),
+    (
+        "PSD_ELASTICSEARCH_INDEX",
+        "pg-stat-activity",
+        "the index to write to",
+    ),
+    (
+        "PSD_KAFKA_BROKERS",
+        "",
+        "produce each activity row to Kafka, as JSON, keyed by pid, e.g. localhost:9092",
+    ),
+    (
+        "PSD_KAFKA_TOPIC",
+        "pg-stat-activity",
+        "the topic to produce activity rows to",
+    ),
+    (
+        "PSD_NATS_URL",
+        "",
+        "publish each activity row to NATS, as JSON, e.g. nats://localhost:4222",
+    ),
+    (
+        "PSD_NATS_SUBJECT",
+        "pg-stat-dump.activity",
+        "the subject to publish activity rows to",
+    ),
+    (
+        "PSD_REDIS_URL",
+        "",
+        "add each activity snapshot, as JSON, to a sorted set in redis, e.g. redis://localhost/0",
+    ),
+    (
+        "PSD_REDIS_KEY_PREFIX",
+        "pg-stat-dump:",
+        "the sorted set is this followed by activity",
+    ),
+    (
+        "PSD_REDIS_KEEP_SECS",
+        "3600",
+        "remove snapshots older than this from the sorted set",
+    ),
+    (
+        "PSD_WEBSOCKET_ADDR",
+        "",
+        "serve each activity snapshot, as JSON, to WebSocket clients, e.g. 127.0.0.1:9090",
+    ),
+    (
+        "PSD_AUTO_CANCEL_SECS",
+        "",
+        "cancel queries which have been running for longer than this, with pg_cancel_backend",
+    ),
+    (
+        "PSD_AUTO_CANCEL_DBNAME",
+        "",
+        "the only database PSD_AUTO_CANCEL_SECS cancels queries in; required with it",
+    ),
+    (
+        "PSD_AUTO_TERMINATE_IDLE_TXN_SECS",
+        "",
+        "terminate sessions idle in a transaction for longer than this, with pg_terminate_backend",
+    ),
+    (
+        "PSD_AUTO_TERMINATE_DBNAME",
+        "",
+        "the only database PSD_AUTO_TERMINATE_IDLE_TXN_SECS terminates sessions in; required with it",
+    ),
+    (
+        "PSD_EXPLAIN_LONG_QUERY_SECS",
+        "",
+        "write the plan of each query running for longer than this, into explain files",
+    ),
+    (
+        "PSD_HTTP_ADDR",
+        "",
+        "serve the latest activity snapshot at /snapshot, its metrics at /metrics, and /health, e.g. 0.0.0.0:8080",
+    ),
+    (
+        "PSD_OTLP_ENDPOINT",
+        "",
+        "send a trace of each poll here, e.g. http://localhost:4318/v1/traces",
+    ),
+];
+
+/// Every setting for a query in the `PSD_CONFIG_FILE`: name, example, and what it does.
+const QUERY_OPTIONS: &[(&str, &str, &str)] = &[
+    (
+        "sql",
+        r#""select now() as snapshot_at, datname, xact_commit from pg_stat_database""#,
+        "required; the first column should be a timestamptz",
+    ),
+    (
+        "output_file_prefix",
+        r#""stat-database""#,
+        "defaults to stat-<name>",
+    ),
+    (
+        "poll_interval_secs",
+        "53",
+        "defaults to PSD_POLL_INTERVAL_SECS",
+    ),
+    (
+        "output_format",
+        r#""json""#,
+        "defaults to PSD_OUTPUT_FORMAT",
+    ),
+    (
+        "key_columns",
+        r#"["datname"]"#,
+        "identify a row between polls, for PSD_DELTA_MODE",
+    ),
+    (
+        "counter_columns",
+        r#"["xact_commit"]"#,
+        "reported as the difference since the last poll, in PSD_DELTA_MODE",
+    ),
+];
+
+/// A commented `PSD_CONFIG_FILE`, headed by the environment variables, which can't be set in it.
+pub fn template() -> String {
+    let mut buf = String::with_capacity(4096);
+    buf.push_str("# pg-stat-dump is configured by environment variables:\n#\n");
+    for (name, default, description) in ENV_VARS {
+        buf.push_str(&format!("# {}={}\n#   {}\n", name, default, description));
+    }
+    buf.push_str("#\n# ...and, in the PSD_CONFIG_FILE, any additional queries:\n\n");
+    buf.push_str("[queries.database]\n");
+    for (name, example, description) in QUERY_OPTIONS {
+        buf.push_str(&format!("# {}\n{} = {}\n", description, name, example));
+    }
+    buf
+}
+
+#[cfg(test)]
+mod tests {
+    use regex::Regex;
+
+    use super::{template, ENV_VARS};
+    use crate::ConfigFile;
+
+    #[test]
+    fn every_env_var_is_documented() {
+        let source = include_str!("main.rs");
+        let read = Regex::new(r#"[^!]\(\s*"(PSD_\w+)""#).unwrap();
+        for name in read.captures_iter(source).map(|c| c[1].to_string()) {
+            assert!(
+                ENV_VARS.iter().any(|(var, _, _)| *var == name),
+                "{} is missing from the template",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn template_parses() {
+        let parsed: ConfigFile = toml::from_str(&template()).unwrap();
+        assert!(parsed.queries.contains_key("database"));
+    }
+}
@@ -0,0 +1,135 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use chrono::{DateTime, SecondsFormat, Utc};
+
+use crate::cell::Cell;
+use crate::replay::{Item, Reader};
+
+/// Print a chart of each `pid` in an activity file, one character per snapshot, showing the
+/// first letter of its `state` (`A`ctive, `I`dle...), or a space if it wasn't there.
+pub fn timeline(path: &str) -> Result<()> {
+    let mut timeline = Timeline::default();
+    for item in Reader::open(path)? {
+        if let Item::Snapshot { when, lines } = item? {
+            timeline.add(when, &lines);
+        }
+    }
+    print!("{}", timeline.render());
+    Ok(())
+}
+
+#[derive(Default)]
+pub struct Timeline {
+    first: Option<DateTime<Utc>>,
+    last: Option<DateTime<Utc>>,
+    snapshots: usize,
+    /// In the order we first saw them.
+    pids: Vec<String>,
+    /// The cells for each pid, up to the last snapshot it was in.
+    cells: HashMap<String, String>,
+}
+
+impl Timeline {
+    pub fn add(&mut self, when: Option<DateTime<Utc>>, lines: &[Vec<Cell>]) {
+        if let Some(when) = when {
+            self.first.get_or_insert(when);
+            self.last = Some(when);
+        }
+        let column = self.snapshots;
+        self.snapshots += 1;
+
+        let Some((headers, rows)) = lines.split_first() else {
+            return;
+        };
+        let find = |name: &str| headers.iter().position(|header| header == name);
+        let (Some(pid_col), Some(state_col)) = (find("pid"), find("state")) else {
+            return;
+        };
+
+        for row in rows {
+            let pid = &row[pid_col];
+            let cells = self.cells.entry(pid.to_string()).or_insert_with(|| {
+                self.pids.push(pid.to_string());
+                String::new()
+            });
+            // the state is empty for e.g. background workers, but they're still there
+            let cell = row[state_col]
+                .chars()
+                .next()
+                .filter(char::is_ascii)
+                .map(|c| c.to_ascii_uppercase())
+                .unwrap_or('.');
+            while cells.len() < column {
+                cells.push(' ');
+            }
+            // a pid twice in one snapshot, e.g. from PSD_EXTRA_COLUMNS joins: keep the first
+            if cells.len() == column {
+                cells.push(cell);
+            }
+        }
+    }
+
+    pub fn render(&self) -> String {
+        let mut buf = String::with_capacity(self.pids.len() * (self.snapshots + 10) + 100);
+        let time = |when: Option<DateTime<Utc>>| {
+            when.map(|when| when.to_rfc3339_opts(SecondsFormat::Secs, true))
+                .unwrap_or_default()
+        };
+        buf.push_str(&format!(
+            "# {} snapshots, {} to {}\n",
+            self.snapshots,
+            time(self.first),
+            time(self.last)
+        ));
+
+        let width = self
+            .pids
+            .iter()
+            .map(|pid| pid.len())
+            .max()
+            .unwrap_or(3)
+            .max(3);
+        buf.push_str(&format!("{:>width$} |\n", "pid", width = width));
+        for pid in &self.pids {
+            buf.push_str(&format!(
+                "{:>width$} |{}\n",
+                pid,
+                self.cells[pid].trim_end(),
+                width = width
+            ));
+        }
+        buf
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Timeline;
+    use crate::printer::table;
+
+    #[test]
+    fn bars() {
+        let mut timeline = Timeline::default();
+        timeline.add(None, &table(&[&["pid", "state"], &["10", "active"]]));
+        timeline.add(
+            None,
+            &table(&[
+                &["pid", "state"],
+                &["10", "idle in transaction"],
+                &["7", ""],
+            ]),
+        );
+        timeline.add(None, &table(&[&["pid", "state"]]));
+        timeline.add(None, &table(&[&["pid", "state"], &["7", "active"]]));
+        assert_eq!(
+            concat!(
+                "# 4 snapshots,  to \n",
+                "pid |\n",
+                " 10 |AI\n",
+                "  7 | . A\n",
+            ),
+            timeline.render()
+        );
+    }
+}
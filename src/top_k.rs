@@ -0,0 +1,103 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::time::Duration;
+
+use crate::cell::Cell;
+use crate::clean_ws;
+use crate::printer::ColumnIndex;
+
+/// The longest-running queries seen in any poll, over the whole run, for `slow-queries-*.txt`.
+pub struct TopKTracker {
+    /// Reversed, so the shortest is the one to drop when there are more than `k`.
+    items: BinaryHeap<Reverse<(Duration, String)>>,
+    k: usize,
+}
+
+impl TopKTracker {
+    pub fn new(k: usize) -> TopKTracker {
+        TopKTracker {
+            items: BinaryHeap::with_capacity(k + 1),
+            k,
+        }
+    }
+
+    /// Consider the longest-running active query in an activity snapshot, after its age columns
+    /// have been added.
+    pub fn add(&mut self, lines: &[Vec<Cell>]) {
+        let columns = ColumnIndex::build(&lines[0]);
+        let (Ok(state), Ok(query_age), Ok(query)) = (
+            columns.get("state"),
+            columns.get("query_age_secs"),
+            columns.get("query"),
+        ) else {
+            return;
+        };
+        let longest = lines[1..]
+            .iter()
+            .filter(|row| row[state] == "active")
+            .filter_map(|row| {
+                let secs = row[query_age].parse::<f64>().ok()?;
+                Some((Duration::try_from_secs_f64(secs).ok()?, &row[query]))
+            })
+            .max_by_key(|(age, _)| *age);
+        if let Some((age, query)) = longest {
+            self.insert(age, query);
+        }
+    }
+
+    /// A query still running at the next poll is seen again, for longer; keep only its longest.
+    fn insert(&mut self, age: Duration, query: &str) {
+        let mut items = std::mem::take(&mut self.items).into_vec();
+        if let Some(Reverse(existing)) = items.iter_mut().find(|Reverse((_, q))| q == query) {
+            existing.0 = existing.0.max(age);
+        } else {
+            items.push(Reverse((age, query.to_string())));
+        }
+        self.items = items.into();
+        while self.items.len() > self.k {
+            self.items.pop();
+        }
+    }
+
+    /// One line per query, longest first: its age in seconds, then the query.
+    pub fn render(&self) -> String {
+        let mut items: Vec<_> = self.items.iter().map(|Reverse(item)| item).collect();
+        items.sort_by(|a, b| b.cmp(a));
+        let mut out = String::new();
+        for (age, query) in items {
+            out.push_str(&format!(
+                "{:>10.3}  {}\n",
+                age.as_secs_f64(),
+                clean_ws(query)
+            ));
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TopKTracker;
+    use crate::printer::table;
+
+    #[test]
+    fn keeps_the_longest() {
+        let headers: &[&str] = &["state", "query_age_secs", "query"];
+        let mut tracker = TopKTracker::new(2);
+        for rows in [
+            [
+                &["active", "5.000", "select 5"],
+                &["idle", "60.000", "idle"],
+            ],
+            [&["active", "1.000", "select 1"], &["active", "", "no age"]],
+            [&["active", "7.500", "select 5"], &["active", "2.000", "x"]],
+            [&["active", "3.000", "select 3"], &["active", "2.000", "x"]],
+        ] {
+            tracker.add(&table(&[headers, rows[0], rows[1]]));
+        }
+        assert_eq!(
+            "     7.500  select 5\n     3.000  select 3\n",
+            tracker.render()
+        );
+    }
+}
@@ -0,0 +1,158 @@
+use anyhow::{anyhow, Context, Result};
+
+use crate::cell::Cell;
+use crate::{connect_client, printer, validate_conn_string};
+
+/// Each table's settings, with any per-table `reloptions` taking precedence over the server's.
+const TABLES: &str = concat!(
+    "select n.nspname::text, c.relname::text, s.n_dead_tup, s.n_mod_since_analyze,",
+    " greatest(c.reltuples, 0)::float8,",
+    " coalesce(o.vacuum_threshold, current_setting('autovacuum_vacuum_threshold'))::float8,",
+    " coalesce(o.vacuum_scale_factor,",
+    " current_setting('autovacuum_vacuum_scale_factor'))::float8,",
+    " coalesce(o.analyze_threshold, current_setting('autovacuum_analyze_threshold'))::float8,",
+    " coalesce(o.analyze_scale_factor,",
+    " current_setting('autovacuum_analyze_scale_factor'))::float8",
+    " from pg_stat_user_tables s",
+    " join pg_class c on c.oid = s.relid",
+    " join pg_namespace n on n.oid = c.relnamespace",
+    " left join lateral (select",
+    " max(option_value) filter (where option_name = 'autovacuum_vacuum_threshold')",
+    " as vacuum_threshold,",
+    " max(option_value) filter (where option_name = 'autovacuum_vacuum_scale_factor')",
+    " as vacuum_scale_factor,",
+    " max(option_value) filter (where option_name = 'autovacuum_analyze_threshold')",
+    " as analyze_threshold,",
+    " max(option_value) filter (where option_name = 'autovacuum_analyze_scale_factor')",
+    " as analyze_scale_factor",
+    " from pg_options_to_table(c.reloptions)) o on true",
+);
+
+struct Table {
+    schema: String,
+    name: String,
+    dead_tuples: i64,
+    modified: i64,
+    tuples: f64,
+    vacuum_threshold: f64,
+    vacuum_scale_factor: f64,
+    analyze_threshold: f64,
+    analyze_scale_factor: f64,
+}
+
+/// Print how close each table is to being autovacuumed or autoanalyzed, closest first.
+pub fn vacuum_candidates(conn_string: &str) -> Result<()> {
+    validate_conn_string(conn_string)?;
+    let mut client = connect_client(conn_string)?;
+    let tables: Vec<Table> = client
+        .query(TABLES, &[])
+        .with_context(|| anyhow!("fetching table statistics"))?
+        .iter()
+        .map(|row| Table {
+            schema: row.get(0),
+            name: row.get(1),
+            dead_tuples: row.get(2),
+            modified: row.get(3),
+            tuples: row.get(4),
+            vacuum_threshold: row.get(5),
+            vacuum_scale_factor: row.get(6),
+            analyze_threshold: row.get(7),
+            analyze_scale_factor: row.get(8),
+        })
+        .collect();
+    client.close()?;
+
+    let lines = report(&tables);
+    print!("{}", printer::render(&lines, &mut [0; 6]));
+    Ok(())
+}
+
+fn report(tables: &[Table]) -> Vec<Vec<Cell>> {
+    // autovacuum's own formula: threshold + scale_factor * reltuples
+    let pct = |count: i64, threshold: f64, scale_factor: f64, tuples: f64| -> f64 {
+        count as f64 / (threshold + scale_factor * tuples).max(1.0) * 100.0
+    };
+
+    let mut rows: Vec<(f64, f64, &Table)> = tables
+        .iter()
+        .map(|table| {
+            let vacuum = pct(
+                table.dead_tuples,
+                table.vacuum_threshold,
+                table.vacuum_scale_factor,
+                table.tuples,
+            );
+            let analyze = pct(
+                table.modified,
+                table.analyze_threshold,
+                table.analyze_scale_factor,
+                table.tuples,
+            );
+            (vacuum, analyze, table)
+        })
+        .collect();
+    rows.sort_by(|a, b| {
+        b.0.max(b.1)
+            .total_cmp(&a.0.max(a.1))
+            .then_with(|| (&a.2.schema, &a.2.name).cmp(&(&b.2.schema, &b.2.name)))
+    });
+
+    let mut lines = vec![[
+        "schema",
+        "table",
+        "dead_tuples",
+        "vacuum_pct",
+        "modified",
+        "analyze_pct",
+    ]
+    .iter()
+    .map(|&header| Cell::from(header))
+    .collect::<Vec<_>>()];
+    for (vacuum, analyze, table) in rows {
+        lines.push(vec![
+            table.schema.as_str().into(),
+            table.name.as_str().into(),
+            Cell::number(table.dead_tuples),
+            Cell::number(format!("{:.1}", vacuum)),
+            Cell::number(table.modified),
+            Cell::number(format!("{:.1}", analyze)),
+        ]);
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{report, Table};
+
+    fn table(name: &str, dead_tuples: i64, modified: i64) -> Table {
+        Table {
+            schema: "public".to_string(),
+            name: name.to_string(),
+            dead_tuples,
+            modified,
+            tuples: 1000.0,
+            vacuum_threshold: 50.0,
+            vacuum_scale_factor: 0.2,
+            analyze_threshold: 50.0,
+            analyze_scale_factor: 0.1,
+        }
+    }
+
+    #[test]
+    fn most_urgent_first() {
+        let lines = report(&[table("a", 125, 0), table("b", 0, 135), table("c", 500, 0)]);
+        let summary: Vec<_> = lines[1..]
+            .iter()
+            .map(|row| (row[1].as_str(), row[3].as_str(), row[5].as_str()))
+            .collect();
+        assert_eq!(
+            vec![
+                ("c", "200.0", "0.0"),
+                ("b", "0.0", "90.0"),
+                ("a", "50.0", "0.0")
+            ],
+            summary
+        );
+    }
+}
@@ -0,0 +1,46 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use bunyarrs::{vars, Bunyarr};
+use lazy_static::lazy_static;
+use serde_json::json;
+
+lazy_static! {
+    static ref STARTED: Instant = Instant::now();
+}
+
+/// Milliseconds after `STARTED` at which the main loop last went around.
+static LAST_POLL_AT: AtomicU64 = AtomicU64::new(0);
+
+fn now_millis() -> u64 {
+    STARTED.elapsed().as_millis() as u64
+}
+
+/// Tell the watchdog that the main loop is still making progress.
+pub fn touch() {
+    LAST_POLL_AT.store(now_millis(), Ordering::Relaxed);
+}
+
+/// Kill the process if the main loop stops calling `touch`, e.g. because a fetch has hung in a
+/// way `statement_timeout` didn't catch. We'd rather exit and be restarted than sit there.
+pub fn start_watchdog(interval: Duration) -> JoinHandle<()> {
+    let limit = interval
+        .saturating_mul(2)
+        .saturating_add(Duration::from_secs(30));
+    let limit_millis = u64::try_from(limit.as_millis()).unwrap_or(u64::MAX);
+
+    touch();
+
+    std::thread::spawn(move || loop {
+        std::thread::sleep(Duration::from_secs(1));
+        let stalled_millis = now_millis().saturating_sub(LAST_POLL_AT.load(Ordering::Relaxed));
+        if stalled_millis > limit_millis {
+            Bunyarr::with_name("watchdog").error(
+                vars! { stalled_millis, limit_millis },
+                "main loop stalled, dying",
+            );
+            std::process::exit(7);
+        }
+    })
+}
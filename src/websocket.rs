@@ -0,0 +1,153 @@
+use std::io::ErrorKind;
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc::{self, SyncSender, TryRecvError};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::{anyhow, Context, Result};
+use bunyarrs::{vars, vars_dbg, Bunyarr};
+use serde_json::json;
+use tungstenite::Message;
+
+/// Messages waiting for a slow client, after which it's dropped.
+const CLIENT_QUEUE: usize = 16;
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long to wait for the client to say something, before checking for new messages.
+const READ_POLL: Duration = Duration::from_millis(100);
+
+#[derive(Default)]
+struct Clients {
+    senders: Vec<SyncSender<Message>>,
+    /// For clients which connect between snapshots.
+    last: Option<Message>,
+}
+
+/// Sends each message to every connected WebSocket client, each on its own thread, so a slow
+/// client can't hold up polling. Between messages, the thread reads from its client, to answer
+/// pings and closes.
+pub struct Broadcaster {
+    clients: Arc<Mutex<Clients>>,
+}
+
+impl Broadcaster {
+    /// Listen on `addr`, accepting clients in the background.
+    pub fn start(addr: &str) -> Result<Broadcaster> {
+        let listener =
+            TcpListener::bind(addr).with_context(|| anyhow!("listening on {:?}", addr))?;
+        Broadcaster::accept(listener)
+    }
+
+    fn accept(listener: TcpListener) -> Result<Broadcaster> {
+        let logger = Bunyarr::with_name("websocket");
+        let address = listener.local_addr()?.to_string();
+        logger.info(vars! { address }, "accepting websocket clients");
+
+        let clients = Arc::new(Mutex::new(Clients::default()));
+        let shared = Arc::clone(&clients);
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let shared = Arc::clone(&shared);
+                let Ok(stream) = stream else {
+                    continue;
+                };
+                // the handshake reads from the client, which might be slow about it
+                std::thread::spawn(move || {
+                    let peer = stream.peer_addr();
+                    if let Err(err) = serve(stream, &shared) {
+                        let peer = format!("{:?}", peer);
+                        Bunyarr::with_name("websocket")
+                            .info(vars_dbg! { err, peer }, "websocket client went away");
+                    }
+                });
+            }
+        });
+        Ok(Broadcaster { clients })
+    }
+
+    /// Clients which have gone away, or fallen too far behind, are forgotten.
+    pub fn broadcast(&self, message: &str) {
+        let message = Message::text(message);
+        let mut clients = self.clients.lock().expect("websocket clients lock");
+        clients
+            .senders
+            .retain(|sender| sender.try_send(message.clone()).is_ok());
+        clients.last = Some(message);
+    }
+}
+
+fn serve(stream: TcpStream, clients: &Mutex<Clients>) -> Result<()> {
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    stream.set_write_timeout(Some(TIMEOUT))?;
+    let mut socket =
+        tungstenite::accept(stream).map_err(|err| anyhow!("handshake failed: {}", err))?;
+    socket.get_ref().set_read_timeout(Some(READ_POLL))?;
+
+    let (sender, receiver) = mpsc::sync_channel::<Message>(CLIENT_QUEUE);
+    {
+        let mut clients = clients.lock().expect("websocket clients lock");
+        if let Some(last) = &clients.last {
+            sender.try_send(last.clone())?;
+        }
+        clients.senders.push(sender);
+    }
+
+    loop {
+        loop {
+            match receiver.try_recv() {
+                Ok(message) => socket.send(message).with_context(|| anyhow!("sending"))?,
+                Err(TryRecvError::Empty) => break,
+                // `broadcast` has forgotten about us
+                Err(TryRecvError::Disconnected) => return Ok(()),
+            }
+        }
+        // which is also what answers pings, and closes; anything else the client says is ignored
+        match socket.read() {
+            Ok(_) => (),
+            Err(tungstenite::Error::Io(err))
+                if matches!(err.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {}
+            Err(tungstenite::Error::ConnectionClosed) => return Ok(()),
+            Err(err) => return Err(anyhow!(err).context("reading")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{TcpListener, TcpStream};
+
+    use tungstenite::{Error, Message};
+
+    use super::Broadcaster;
+
+    #[test]
+    fn late_clients_get_the_last_message() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let broadcaster = Broadcaster::accept(listener).unwrap();
+        broadcaster.broadcast("first");
+
+        // a client which never finishes its handshake doesn't get in the way
+        let _slow = TcpStream::connect(addr).unwrap();
+
+        let (mut client, response) = tungstenite::connect(format!("ws://{}/", addr)).unwrap();
+        assert_eq!(101, response.status().as_u16());
+        assert_eq!(Message::text("first"), client.read().unwrap());
+
+        client.send(Message::Ping("hello".into())).unwrap();
+        assert_eq!(Message::Pong("hello".into()), client.read().unwrap());
+
+        broadcaster.broadcast("second");
+        assert_eq!(Message::text("second"), client.read().unwrap());
+
+        client.close(None).unwrap();
+        loop {
+            match client.read() {
+                Ok(Message::Close(_)) => (),
+                Err(Error::ConnectionClosed) => break,
+                other => panic!("{:?}", other),
+            }
+        }
+    }
+}
@@ -0,0 +1,356 @@
+use std::fs;
+use std::io::{self, Write};
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, bail, Context, Result};
+use bunyarrs::{vars_dbg, Bunyarr};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+
+use crate::backpressure::BackpressureWriter;
+use crate::cell::Cell;
+use crate::influx;
+
+/// zstd skips frames with magic numbers 0x184D2A50 to 0x184D2A5F, so we can stash our own data.
+const SKIPPABLE_FRAME_MAGIC: u32 = 0x184D2A50;
+
+#[derive(Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    Json,
+    /// Each snapshot is an array of rows, header row first; each cell is nil, a string or a
+    /// number, as in the JSON.
+    Msgpack,
+    /// InfluxDB line protocol, for Telegraf; anything other than snapshots is a `#` comment.
+    Influx,
+}
+
+impl OutputFormat {
+    pub fn from_name(name: &str) -> Result<OutputFormat> {
+        Ok(match name {
+            "json" => OutputFormat::Json,
+            "msgpack" => OutputFormat::Msgpack,
+            "influx" => OutputFormat::Influx,
+            other => bail!(
+                "unrecognised output format {:?}, expected json, msgpack or influx",
+                other
+            ),
+        })
+    }
+
+    /// Guess from a file name written by `extension()`.
+    pub fn from_path(path: &str) -> Result<OutputFormat> {
+        let name = path.strip_suffix(".zst").unwrap_or(path);
+        Ok(match name.rsplit('.').next() {
+            Some("jsonl") => OutputFormat::Json,
+            Some("msgpack") => OutputFormat::Msgpack,
+            Some("influx") => OutputFormat::Influx,
+            _ => bail!("can't tell the format of {:?} from its name", path),
+        })
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            OutputFormat::Json => "jsonl",
+            OutputFormat::Msgpack => "msgpack",
+            OutputFormat::Influx => "influx",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Line {
+    pub when: Option<DateTime<Utc>>,
+    pub records: Vec<Map<String, Value>>,
+}
+
+/// Written in a skippable frame at the end of the file, so tools can find out what's in the
+/// file without decompressing it.
+#[derive(Serialize, Deserialize)]
+pub struct Metadata {
+    pub total_rows: u64,
+    pub snapshots: u64,
+    pub start_ts: Option<DateTime<Utc>>,
+    pub end_ts: Option<DateTime<Utc>>,
+}
+
+/// Where a `SnapshotWriter` puts its compressed bytes; files, except in tests.
+pub trait OutputOpener {
+    fn open(&self, path: &str) -> Result<Box<dyn Write + Send>>;
+}
+
+pub struct FileOpener;
+
+impl OutputOpener for FileOpener {
+    fn open(&self, path: &str) -> Result<Box<dyn Write + Send>> {
+        let file = fs::File::create(path).with_context(|| anyhow!("creating {:?}", path))?;
+        Ok(Box::new(file))
+    }
+}
+
+/// Counts the bytes which make it to `inner`.
+struct Counted {
+    inner: Box<dyn Write + Send>,
+    written: u64,
+}
+
+impl Write for Counted {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.written += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Writes everything to `primary`, and also to `secondary`, if there is one. Failing to write
+/// to `secondary` is only logged, after which we stop trying; e.g. a FIFO's reader went away.
+pub struct TeeWriter<W> {
+    primary: W,
+    secondary: Option<Box<dyn Write + Send>>,
+    /// Bytes accepted by `primary`.
+    written: u64,
+}
+
+impl<W: Write> TeeWriter<W> {
+    pub fn new(primary: W) -> TeeWriter<W> {
+        TeeWriter {
+            primary,
+            secondary: None,
+            written: 0,
+        }
+    }
+
+    pub fn into_primary(self) -> W {
+        self.primary
+    }
+
+    fn secondary_failed(&mut self, err: io::Error) {
+        Bunyarr::with_name("writer").warn(vars_dbg! { err }, "giving up on extra output file");
+        self.secondary = None;
+    }
+}
+
+impl<W: Write> Write for TeeWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.primary.write(buf)?;
+        self.written += written as u64;
+        if let Some(secondary) = &mut self.secondary {
+            if let Err(err) = secondary.write_all(&buf[..written]) {
+                self.secondary_failed(err);
+            }
+        }
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.primary.flush()?;
+        if let Some(secondary) = &mut self.secondary {
+            if let Err(err) = secondary.flush() {
+                self.secondary_failed(err);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Totals for one or more finished files.
+#[derive(Default)]
+pub struct Summary {
+    pub snapshots: u64,
+    pub rows: u64,
+    pub uncompressed_bytes: u64,
+    pub compressed_bytes: u64,
+    /// Snapshots not written because the write queue was full.
+    pub dropped_snapshots: u64,
+}
+
+impl Summary {
+    pub fn add(&mut self, other: Summary) {
+        self.snapshots += other.snapshots;
+        self.rows += other.rows;
+        self.uncompressed_bytes += other.uncompressed_bytes;
+        self.compressed_bytes += other.compressed_bytes;
+        self.dropped_snapshots += other.dropped_snapshots;
+    }
+}
+
+/// A compressed output file, and what has been written to it.
+pub struct SnapshotWriter {
+    path: String,
+    output: BackpressureWriter<TeeWriter<zstd::Encoder<'static, Counted>>>,
+    format: OutputFormat,
+    /// The InfluxDB measurement name, for that format.
+    measurement: String,
+    metadata: Metadata,
+    opened_at: Instant,
+    /// Only flush the compressor once this many bytes have been written since the last flush.
+    compress_after: Option<usize>,
+    unflushed: usize,
+}
+
+impl SnapshotWriter {
+    /// With a `write_queue`, compression happens on another thread, and snapshots are dropped
+    /// if it falls that many behind. Without `compress_after`, every snapshot is flushed.
+    pub fn create(
+        opener: &dyn OutputOpener,
+        path: String,
+        format: OutputFormat,
+        measurement: String,
+        write_queue: Option<usize>,
+        compress_after: Option<usize>,
+    ) -> Result<SnapshotWriter> {
+        let file = Counted {
+            inner: opener.open(&path)?,
+            written: 0,
+        };
+        let output =
+            BackpressureWriter::new(TeeWriter::new(zstd::Encoder::new(file, 9)?), write_queue);
+        Ok(SnapshotWriter {
+            path,
+            output,
+            format,
+            measurement,
+            metadata: Metadata {
+                total_rows: 0,
+                snapshots: 0,
+                start_ts: None,
+                end_ts: None,
+            },
+            opened_at: Instant::now(),
+            compress_after,
+            unflushed: 0,
+        })
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    pub fn age(&self) -> Duration {
+        self.opened_at.elapsed()
+    }
+
+    /// Also write everything, uncompressed, to `extra`.
+    pub fn set_extra_output(&mut self, extra: Box<dyn Write + Send>) -> Result<()> {
+        self.output.inner_mut()?.secondary = Some(extra);
+        Ok(())
+    }
+
+    /// For moving to the next file, on rotation.
+    pub fn take_extra_output(&mut self) -> Result<Option<Box<dyn Write + Send>>> {
+        Ok(self.output.inner_mut()?.secondary.take())
+    }
+
+    /// Write something other than a snapshot, e.g. a header.
+    pub fn write_line(&mut self, line: &impl Serialize) -> Result<()> {
+        let buf = encode_line(self.format, line)?;
+        self.output.write(buf, false)
+    }
+
+    /// Write a `# ` line, which readers skip; a plain string, in msgpack.
+    pub fn write_comment(&mut self, comment: &str) -> Result<()> {
+        let buf = match self.format {
+            OutputFormat::Json | OutputFormat::Influx => format!("# {}\n", comment).into_bytes(),
+            OutputFormat::Msgpack => rmp_serde::encode::to_vec(comment)?,
+        };
+        self.output.write(buf, false)
+    }
+
+    pub fn write_snapshot(
+        &mut self,
+        when: Option<DateTime<Utc>>,
+        lines: &[Vec<Cell>],
+    ) -> Result<()> {
+        let buf = match self.format {
+            OutputFormat::Json => encode_line(
+                self.format,
+                &Line {
+                    when,
+                    records: records(lines),
+                },
+            )?,
+            OutputFormat::Msgpack => rmp_serde::encode::to_vec(lines)?,
+            OutputFormat::Influx => influx::snapshot(&self.measurement, when, lines).into_bytes(),
+        };
+        // each flush ends a zstd block, which is expensive for small snapshots
+        let unflushed = self.unflushed + buf.len();
+        let flush = self.compress_after.is_none_or(|after| unflushed >= after);
+        if !self
+            .output
+            .try_write(buf, flush)
+            .with_context(|| anyhow!("writing compressed data"))?
+        {
+            return Ok(());
+        }
+        self.unflushed = if flush { 0 } else { unflushed };
+
+        self.metadata.snapshots += 1;
+        self.metadata.total_rows += lines.len().saturating_sub(1) as u64;
+        if when.is_some() {
+            self.metadata.start_ts = self.metadata.start_ts.or(when);
+            self.metadata.end_ts = when;
+        }
+        Ok(())
+    }
+
+    pub fn finish(self) -> Result<Summary> {
+        let dropped_snapshots = self.output.dropped();
+        let output = self.output.into_inner()?;
+        let uncompressed_bytes = output.written;
+        let mut file = output
+            .into_primary()
+            .finish()
+            .with_context(|| anyhow!("finalising {:?}", self.path))?;
+
+        let metadata = serde_json::to_vec(&self.metadata)?;
+        file.write_all(&SKIPPABLE_FRAME_MAGIC.to_le_bytes())?;
+        file.write_all(&u32::try_from(metadata.len())?.to_le_bytes())?;
+        file.write_all(&metadata)?;
+        file.flush()
+            .with_context(|| anyhow!("writing metadata to {:?}", self.path))?;
+
+        Ok(Summary {
+            snapshots: self.metadata.snapshots,
+            rows: self.metadata.total_rows,
+            uncompressed_bytes,
+            compressed_bytes: file.written,
+            dropped_snapshots,
+        })
+    }
+}
+
+fn encode_line(format: OutputFormat, line: &impl Serialize) -> Result<Vec<u8>> {
+    let mut buf = Vec::with_capacity(1024);
+    match format {
+        OutputFormat::Json => {
+            serde_json::to_writer(&mut buf, line)?;
+            buf.push(b'\n');
+        }
+        OutputFormat::Msgpack => rmp_serde::encode::write_named(&mut buf, line)?,
+        OutputFormat::Influx => {
+            buf.extend_from_slice(b"# ");
+            serde_json::to_writer(&mut buf, line)?;
+            buf.push(b'\n');
+        }
+    }
+    Ok(buf)
+}
+
+/// Each row as an object, keyed by header, with NULLs as `null` and numbers as numbers.
+pub fn records(lines: &[Vec<Cell>]) -> Vec<Map<String, Value>> {
+    let (headers, rows) = lines.split_first().expect("header row");
+    rows.iter()
+        .map(|row| {
+            headers
+                .iter()
+                .zip(row)
+                .map(|(header, value)| (header.to_string(), value.to_json()))
+                .collect()
+        })
+        .collect()
+}